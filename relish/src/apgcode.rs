@@ -0,0 +1,403 @@
+use crate::automaton::CellularAutomaton;
+
+use crate::classify::{classify, Classification, Pattern};
+use crate::isotropic::{LifeLikeCellularAutomaton, LifeLikeRule};
+
+// Column values of a five-row strip, top row in the lowest bit
+const STRIP_DIGITS: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+// Lengths of runs of empty columns after `y`, starting from 4
+const RUN_DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+// The code for objects that never settle into a repeating shape
+pub const PATHOLOGICAL: &str = "PATHOLOGICAL";
+
+// Empty space the object is given on every side while it is identified
+const PADDING: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApgcodeError {
+    // Not `xs`, `xp` or `xq` and a number, followed by `_`
+    InvalidPrefix,
+    // A character of the Wechsler code that means nothing where it stands
+    InvalidCharacter(char),
+    // A code with no live cells
+    Empty,
+    // The object does not fit in the world asked for
+    WorldSize,
+}
+
+// What the prefix of an apgcode says about the object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApgcodeKind {
+    StillLife { population: usize },
+    Oscillator { period: usize },
+    Spaceship { period: usize },
+}
+
+fn push_empty_columns(code: &mut String, mut count: usize) {
+    while count >= 40 {
+        code.push_str("yz");
+        count -= 39;
+    }
+    match count {
+        0 => {}
+        1 => code.push('0'),
+        2 => code.push('w'),
+        3 => code.push('x'),
+        _ => {
+            code.push('y');
+            code.push(RUN_DIGITS[count - 4] as char);
+        }
+    }
+}
+
+// Extended Wechsler format of the pattern as it stands: strips of five rows
+// from the top separated by `z`, each written column by column with runs of
+// empty columns shortened and trailing ones left out
+pub fn wechsler(pattern: &Pattern<bool>) -> String {
+    let cells = pattern.cells();
+    let height = pattern.height();
+    let mut code = String::new();
+
+    for strip in 0..height.div_ceil(5) {
+        if strip > 0 {
+            code.push('z');
+        }
+
+        let rows = &cells[5 * strip..height.min(5 * strip + 5)];
+        let columns = (0..pattern.width()).map(|j| {
+            rows.iter()
+                .enumerate()
+                .filter(|(_, row)| row[j])
+                .fold(0, |value, (k, _)| value | (1 << k))
+        });
+
+        let mut empty = 0;
+        for value in columns {
+            if value == 0 {
+                empty += 1;
+            } else {
+                push_empty_columns(&mut code, empty);
+                empty = 0;
+                code.push(STRIP_DIGITS[value] as char);
+            }
+        }
+    }
+
+    code
+}
+
+// Shorter codes win, then the earlier one in ASCII order
+fn better(a: String, b: String) -> String {
+    if (b.len(), &b) < (a.len(), &a) {
+        b
+    } else {
+        a
+    }
+}
+
+// The best Wechsler code over all eight rotations and reflections
+pub fn canonical_wechsler(pattern: &Pattern<bool>) -> String {
+    (0..4)
+        .flat_map(|rotations| [false, true].map(|reflect| pattern.transformed(rotations, reflect)))
+        .map(|p| wechsler(&p))
+        .reduce(better)
+        .expect("There are always eight transformations")
+}
+
+fn padded_world(pattern: &Pattern<bool>) -> Vec<Vec<bool>> {
+    let mut world =
+        vec![vec![false; pattern.width() + 2 * PADDING]; pattern.height() + 2 * PADDING];
+    for (i, row) in pattern.cells().iter().enumerate() {
+        world[PADDING + i][PADDING..PADDING + row.len()].copy_from_slice(row);
+    }
+    world
+}
+
+// The apgcode of an isolated object under `rule`, e.g. `xs4_33` for the
+// block or `xq4_153` for the glider: `xs` and the population for still
+// lifes, `xp` or `xq` and the period for oscillators and spaceships, then
+// the best Wechsler code over every phase and orientation. Objects that do
+// not repeat within `max_period` generations are `PATHOLOGICAL`.
+pub fn apgcode(pattern: &Pattern<bool>, rule: &LifeLikeRule, max_period: usize) -> String {
+    let world = padded_world(pattern);
+    let new_ca = || {
+        LifeLikeCellularAutomaton(world.clone(), rule.clone(), true).expect("Construction failed")
+    };
+
+    let (prefix, period) = match classify(&mut new_ca(), true, max_period) {
+        Classification::StillLife => (format!("xs{}", pattern.population()), 1),
+        Classification::Oscillator { period } => (format!("xp{}", period), period),
+        Classification::Spaceship { period, .. } => (format!("xq{}", period), period),
+        Classification::Empty | Classification::Unknown => return PATHOLOGICAL.into(),
+    };
+
+    let mut ca = new_ca();
+    let mut best = canonical_wechsler(pattern);
+    for _ in 1..period {
+        ca.step();
+        let (phase, _) = Pattern::from_world(&ca.world(), true).expect("Repeating objects live");
+        best = better(best, canonical_wechsler(&phase));
+    }
+
+    format!("{}_{}", prefix, best)
+}
+
+// The live cells of a Wechsler code, cropped to their bounding box
+pub fn decode_wechsler(code: &str) -> Result<Vec<Vec<bool>>, ApgcodeError> {
+    let mut strips: Vec<Vec<usize>> = vec![Vec::new()];
+    let mut chars = code.chars();
+
+    while let Some(c) = chars.next() {
+        let strip = strips.last_mut().expect("There is always a strip");
+        match c {
+            'w' => strip.extend([0; 2]),
+            'x' => strip.extend([0; 3]),
+            'y' => {
+                let run = chars
+                    .next()
+                    .and_then(|d| RUN_DIGITS.iter().position(|&r| r as char == d))
+                    .ok_or(ApgcodeError::InvalidCharacter('y'))?;
+                strip.resize(strip.len() + run + 4, 0);
+            }
+            'z' => strips.push(Vec::new()),
+            _ => strip.push(
+                STRIP_DIGITS
+                    .iter()
+                    .position(|&d| d as char == c)
+                    .ok_or(ApgcodeError::InvalidCharacter(c))?,
+            ),
+        }
+    }
+
+    let width = strips.iter().map(|strip| strip.len()).max().unwrap_or(0);
+    let mut world = vec![vec![false; width]; 5 * strips.len()];
+    for (s, strip) in strips.iter().enumerate() {
+        for (j, &value) in strip.iter().enumerate() {
+            for k in 0..5 {
+                world[5 * s + k][j] = (value >> k) & 1 == 1;
+            }
+        }
+    }
+
+    Pattern::from_world(&world, false)
+        .map(|(pattern, _)| pattern.cells().clone())
+        .ok_or(ApgcodeError::Empty)
+}
+
+// Splits an apgcode such as `xq4_153` into its kind and the live cells of
+// the phase it records
+pub fn decode_apgcode(code: &str) -> Result<(ApgcodeKind, Vec<Vec<bool>>), ApgcodeError> {
+    let (prefix, wechsler) = code.split_once('_').ok_or(ApgcodeError::InvalidPrefix)?;
+    let number = |digits: &str| {
+        digits
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0 && !digits.starts_with('+'))
+            .ok_or(ApgcodeError::InvalidPrefix)
+    };
+
+    let kind = if let Some(digits) = prefix.strip_prefix("xs") {
+        ApgcodeKind::StillLife {
+            population: number(digits)?,
+        }
+    } else if let Some(digits) = prefix.strip_prefix("xp") {
+        ApgcodeKind::Oscillator {
+            period: number(digits)?,
+        }
+    } else if let Some(digits) = prefix.strip_prefix("xq") {
+        ApgcodeKind::Spaceship {
+            period: number(digits)?,
+        }
+    } else {
+        return Err(ApgcodeError::InvalidPrefix);
+    };
+
+    Ok((kind, decode_wechsler(wechsler)?))
+}
+
+// The object of an apgcode in the middle of an empty `height` x `width`
+// world, ready for `LifeLikeCellularAutomaton` or `ConwayCellularAutomaton`
+pub fn apgcode_world(
+    code: &str,
+    height: usize,
+    width: usize,
+) -> Result<Vec<Vec<bool>>, ApgcodeError> {
+    let (_, cells) = decode_apgcode(code)?;
+    let cells_width = cells[0].len();
+    if cells.len() > height || cells_width > width {
+        return Err(ApgcodeError::WorldSize);
+    }
+
+    let top = (height - cells.len()) / 2;
+    let left = (width - cells_width) / 2;
+    let mut world = vec![vec![false; width]; height];
+    for (i, row) in cells.iter().enumerate() {
+        world[top + i][left..left + cells_width].copy_from_slice(row);
+    }
+    Ok(world)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn life() -> LifeLikeRule {
+        LifeLikeRule::parse("B3/S23").expect("Parsing failed")
+    }
+
+    // Reads rows of `.` and `o`
+    fn pattern(rows: &[&str]) -> Pattern<bool> {
+        let world: Vec<Vec<bool>> = rows
+            .iter()
+            .map(|row| row.chars().map(|c| c == 'o').collect())
+            .collect();
+        Pattern::from_world(&world, false).expect("Nothing alive").0
+    }
+
+    #[test]
+    fn test_wechsler() {
+        assert_eq!(wechsler(&pattern(&["oo", "oo"])), "33");
+        assert_eq!(wechsler(&pattern(&["o....o"])), "1y01");
+        assert_eq!(wechsler(&pattern(&["o", ".", ".", ".", ".", "o"])), "1z1");
+        assert_eq!(wechsler(&pattern(&["o.o..o...o"])), "101w1x1");
+
+        let mut far = vec!['.'; 45];
+        far[0] = 'o';
+        far[44] = 'o';
+        let far: String = far.into_iter().collect();
+        assert_eq!(wechsler(&pattern(&[&far])), "1yzy01");
+    }
+
+    #[test]
+    fn test_still_lifes() {
+        let rule = life();
+        let cases: [(&[&str], &str); 6] = [
+            (&["oo", "oo"], "xs4_33"),
+            (&[".oo.", "o..o", ".oo."], "xs6_696"),
+            (&["oo.", "o.o", ".o."], "xs5_253"),
+            (&[".o.", "o.o", ".o."], "xs4_252"),
+            (&[".oo.", "o..o", ".o.o", "..o."], "xs7_2596"),
+            (&[".oo.", "o..o", "o..o", ".oo."], "xs8_6996"),
+        ];
+
+        for (rows, code) in cases {
+            assert_eq!(apgcode(&pattern(rows), &rule, 30), code);
+        }
+    }
+
+    #[test]
+    fn test_oscillators_and_spaceships() {
+        let rule = life();
+        let cases: [(&[&str], &str); 5] = [
+            (&["ooo"], "xp2_7"),
+            (&[".ooo", "ooo."], "xp2_7e"),
+            (&["oo..", "oo..", "..oo", "..oo"], "xp2_318c"),
+            (&[".o.", "..o", "ooo"], "xq4_153"),
+            (&[".o..o", "o....", "o...o", "oooo."], "xq4_6frc"),
+        ];
+
+        for (rows, code) in cases {
+            assert_eq!(apgcode(&pattern(rows), &rule, 30), code);
+        }
+    }
+
+    #[test]
+    fn test_orientation_and_phase_do_not_matter() {
+        let rule = life();
+        let glider = pattern(&[".o.", "..o", "ooo"]);
+        let other_phase = pattern(&["o.o", ".oo", ".o."]);
+
+        for rotations in 0..4 {
+            for reflect in [false, true] {
+                assert_eq!(
+                    apgcode(&glider.transformed(rotations, reflect), &rule, 30),
+                    "xq4_153"
+                );
+            }
+        }
+        assert_eq!(apgcode(&other_phase, &rule, 30), "xq4_153");
+    }
+
+    #[test]
+    fn test_pathological() {
+        // The R-pentomino needs over a thousand generations to settle
+        let r_pentomino = pattern(&[".oo", "oo.", ".o."]);
+        assert_eq!(apgcode(&r_pentomino, &life(), 30), PATHOLOGICAL);
+
+        // A lone cell dies
+        assert_eq!(apgcode(&pattern(&["o"]), &life(), 30), PATHOLOGICAL);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode_apgcode("xs4_33"),
+            Ok((
+                ApgcodeKind::StillLife { population: 4 },
+                vec![vec![true, true], vec![true, true]]
+            ))
+        );
+
+        let (kind, glider) = decode_apgcode("xq4_153").expect("Decoding failed");
+        assert_eq!(kind, ApgcodeKind::Spaceship { period: 4 });
+        assert_eq!(
+            Pattern::from_world(&glider, false).map(|(p, _)| p),
+            Some(pattern(&["ooo", "..o", ".o."]))
+        );
+
+        assert_eq!(
+            decode_wechsler("1yzy01"),
+            decode_wechsler(&format!("1{}1", "0".repeat(43)))
+        );
+        assert_eq!(decode_wechsler("1z1").map(|w| w.len()), Ok(6));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rule = life();
+        for code in [
+            "xs4_33",
+            "xs7_2596",
+            "xp2_318c",
+            "xp15_4r4z4r4",
+            "xq4_6frc",
+            "xq4_153",
+        ] {
+            let (_, cells) = decode_apgcode(code).expect("Decoding failed");
+            let (decoded, _) = Pattern::from_world(&cells, false).expect("Nothing alive");
+            assert_eq!(apgcode(&decoded, &rule, 30), code);
+        }
+    }
+
+    #[test]
+    fn test_apgcode_world() {
+        let world = apgcode_world("xp2_7", 5, 5).expect("Decoding failed");
+        let mut ca =
+            LifeLikeCellularAutomaton(world.clone(), life(), false).expect("Construction failed");
+
+        assert!(world[1][2] && world[2][2] && world[3][2]);
+        ca.step();
+        ca.step();
+        assert_eq!(ca.world(), world);
+
+        assert_eq!(apgcode_world("xq4_153", 2, 5), Err(ApgcodeError::WorldSize));
+    }
+
+    #[test]
+    fn test_invalid_codes() {
+        assert_eq!(decode_apgcode("xs4"), Err(ApgcodeError::InvalidPrefix));
+        assert_eq!(decode_apgcode("xr4_33"), Err(ApgcodeError::InvalidPrefix));
+        assert_eq!(decode_apgcode("xp_7"), Err(ApgcodeError::InvalidPrefix));
+        assert_eq!(decode_apgcode("xp0_7"), Err(ApgcodeError::InvalidPrefix));
+        assert_eq!(
+            decode_apgcode("xs4_3!"),
+            Err(ApgcodeError::InvalidCharacter('!'))
+        );
+        assert_eq!(
+            decode_apgcode("xs4_3y"),
+            Err(ApgcodeError::InvalidCharacter('y'))
+        );
+        assert_eq!(decode_apgcode("xs1_0"), Err(ApgcodeError::Empty));
+    }
+}
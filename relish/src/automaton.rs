@@ -9,3 +9,61 @@ pub trait CellularAutomaton {
 
 #[derive(Debug, Clone)]
 pub struct CellularAutomatonWorldSizeError;
+
+// Which cells around a centre count as its neighbours, for rules and
+// measures that take a range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborhoodShape {
+    // Every cell within the range square
+    Moore,
+    // Cells within the range in Manhattan distance
+    VonNeumann,
+}
+
+impl NeighborhoodShape {
+    // Whether offset `(dy, dx)` of the square of side `2 * range + 1` around
+    // a cell, with the cell at `(range, range)`, lies in the shape
+    pub(crate) fn contains(&self, dy: usize, dx: usize, range: usize) -> bool {
+        match self {
+            NeighborhoodShape::Moore => true,
+            NeighborhoodShape::VonNeumann => dy.abs_diff(range) + dx.abs_diff(range) <= range,
+        }
+    }
+}
+
+// A probability parameter outside [0, 1], or NaN
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidProbabilityError {
+    pub name: &'static str,
+    pub value: f64,
+}
+
+pub(crate) fn check_probability(
+    name: &'static str,
+    value: f64,
+) -> Result<f64, InvalidProbabilityError> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(InvalidProbabilityError { name, value })
+    }
+}
+
+// Why an automaton with parameters besides its world couldn't be built
+#[derive(Debug, Clone)]
+pub enum CellularAutomatonConstructionError {
+    WorldSize(CellularAutomatonWorldSizeError),
+    Probability(InvalidProbabilityError),
+}
+
+impl From<CellularAutomatonWorldSizeError> for CellularAutomatonConstructionError {
+    fn from(err: CellularAutomatonWorldSizeError) -> Self {
+        CellularAutomatonConstructionError::WorldSize(err)
+    }
+}
+
+impl From<InvalidProbabilityError> for CellularAutomatonConstructionError {
+    fn from(err: InvalidProbabilityError) -> Self {
+        CellularAutomatonConstructionError::Probability(err)
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::thread;
+
+use rand::{Rng, SeedableRng};
+
+use crate::automaton::{
+    check_probability, CellularAutomaton, CellularAutomatonWorldSizeError, InvalidProbabilityError,
+    NeighborhoodShape,
+};
+
+use crate::apgcode::apgcode;
+use crate::classify::Pattern;
+use crate::components::{components, Component};
+use crate::dim2::CellularAutomaton2d;
+use crate::isotropic::{LifeLikeCellularAutomaton, LifeLikeRule};
+use crate::stochastic::StochasticRng;
+
+// Live cells closer than this to the edge of the soup's world are taken to
+// be escaping, and are counted and removed before the edge can affect them
+const EDGE: usize = 4;
+
+// Live cells with at most this many empty cells between them can affect
+// each other within a generation, so they are counted as one object
+const GAP: usize = 1;
+
+// Object frequencies over a number of soups, keyed by apgcode
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Census {
+    soups: usize,
+    objects: BTreeMap<String, usize>,
+}
+
+impl Census {
+    pub fn soups(&self) -> usize {
+        self.soups
+    }
+
+    pub fn count(&self, code: &str) -> usize {
+        self.objects.get(code).copied().unwrap_or(0)
+    }
+
+    // Number of objects counted
+    pub fn total(&self) -> usize {
+        self.objects.values().sum()
+    }
+
+    pub fn merge(&mut self, other: &Census) {
+        self.soups += other.soups;
+        for (code, count) in &other.objects {
+            *self.objects.entry(code.clone()).or_insert(0) += count;
+        }
+    }
+
+    // Most common objects first, ties in apgcode order
+    pub fn table(&self) -> Vec<(&str, usize)> {
+        let mut table: Vec<(&str, usize)> = self
+            .objects
+            .iter()
+            .map(|(code, &count)| (code.as_str(), count))
+            .collect();
+        table.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        table
+    }
+
+    fn record(&mut self, code: String) {
+        *self.objects.entry(code).or_insert(0) += 1;
+    }
+}
+
+impl fmt::Display for Census {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let table = self.table();
+        let width = table.iter().map(|(code, _)| code.len()).max().unwrap_or(0);
+
+        writeln!(f, "{} soups, {} objects", self.soups, self.total())?;
+        for (code, count) in table {
+            writeln!(f, "{:<width$} {}", code, count, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+fn world_hash(world: &Vec<Vec<bool>>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    world.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The cells of `component` as they are in `world`, as a pattern
+fn component_pattern(world: &[Vec<bool>], component: &Component<bool>) -> Option<Pattern<bool>> {
+    Pattern::from_world(&component.extract(world), false).map(|(pattern, _)| pattern)
+}
+
+// Runs random soups of a Life-like rule to stabilisation and counts the
+// objects left behind, in the manner of apgsearch. Each soup is placed in
+// the middle of an empty bounded world; spaceships that fly off are counted
+// and removed as they near the edge. Soups are numbered, and soup `n` is the
+// same for a given seed however many threads share the work.
+#[derive(Debug, Clone)]
+pub struct SoupSearch {
+    rule: LifeLikeRule,
+    seed: u64,
+    soup_size: usize,
+    density: f64,
+    margin: usize,
+    max_generations: usize,
+    max_period: usize,
+    threads: usize,
+}
+
+impl SoupSearch {
+    pub fn new(rule: LifeLikeRule, seed: u64) -> Self {
+        Self {
+            rule,
+            seed,
+            soup_size: 16,
+            density: 0.5,
+            margin: 32,
+            max_generations: 10_000,
+            max_period: 60,
+            threads: 1,
+        }
+    }
+
+    // Soups are `size` x `size`
+    pub fn with_soup_size(mut self, size: usize) -> Self {
+        self.soup_size = size;
+        self
+    }
+
+    // Chance of each soup cell starting alive
+    pub fn with_density(mut self, density: f64) -> Result<Self, InvalidProbabilityError> {
+        self.density = check_probability("density", density)?;
+        Ok(self)
+    }
+
+    // Empty space around the soup before the edge of its world
+    pub fn with_margin(mut self, margin: usize) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    // Soups still changing after this many generations are counted as they
+    // are
+    pub fn with_max_generations(mut self, generations: usize) -> Self {
+        self.max_generations = generations;
+        self
+    }
+
+    // The longest period looked for, both for the ash and for objects
+    pub fn with_max_period(mut self, period: usize) -> Self {
+        self.max_period = period.max(1);
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn soup(&self, index: u64) -> Vec<Vec<bool>> {
+        let mut rng = StochasticRng::seed_from_u64(self.seed);
+        rng.set_stream(index);
+
+        (0..self.soup_size)
+            .map(|_| {
+                (0..self.soup_size)
+                    .map(|_| rng.gen_bool(self.density))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Soups `0..soups`, shared out between the threads. Fails if the soups'
+    // worlds would be empty, with no soup cells and no margin.
+    pub fn run(&self, soups: usize) -> Result<Census, CellularAutomatonWorldSizeError> {
+        let threads = self.threads.min(soups).max(1);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|thread| {
+                    scope.spawn(move || {
+                        let mut census = Census::default();
+                        for index in (thread..soups).step_by(threads) {
+                            census.merge(&self.census_soup(&self.soup(index as u64))?);
+                        }
+                        Ok(census)
+                    })
+                })
+                .collect();
+
+            let mut total = Census::default();
+            for handle in handles {
+                total.merge(&handle.join().expect("Soup search thread panicked")?);
+            }
+            Ok(total)
+        })
+    }
+
+    fn automaton(&self, world: Vec<Vec<bool>>) -> CellularAutomaton2d<bool, 3, 3> {
+        LifeLikeCellularAutomaton(world, self.rule.clone(), false).expect("Construction failed")
+    }
+
+    // Counts and clears objects near the edge; true if any were found
+    fn remove_escaping(&self, world: &mut [Vec<bool>], census: &mut Census) -> bool {
+        let height = world.len();
+        let width = world[0].len();
+        // In worlds narrower than two edges every cell is near one
+        let near_edge = |i: usize, j: usize| {
+            i < EDGE
+                || j < EDGE
+                || i >= height.saturating_sub(EDGE)
+                || j >= width.saturating_sub(EDGE)
+        };
+
+        let any_near_edge = world
+            .iter()
+            .enumerate()
+            .any(|(i, row)| (0..width).any(|j| row[j] && near_edge(i, j)));
+        if !any_near_edge {
+            return false;
+        }
+
+        let mut removed = false;
+        for component in components(world, NeighborhoodShape::Moore, GAP, false) {
+            if !component.cells().iter().any(|&(i, j)| near_edge(i, j)) {
+                continue;
+            }
+
+            if let Some(pattern) = component_pattern(world, &component) {
+                census.record(apgcode(&pattern, &self.rule, self.max_period));
+            }
+            for &(i, j) in component.cells() {
+                world[i][j] = false;
+            }
+            removed = true;
+        }
+
+        removed
+    }
+
+    // Runs a single soup, which need not be square, to stabilisation and
+    // counts its objects. The soup's rows must all be the same length, and
+    // it must leave a non-empty world once the margin is added.
+    pub fn census_soup(
+        &self,
+        soup: &[Vec<bool>],
+    ) -> Result<Census, CellularAutomatonWorldSizeError> {
+        let soup_width = soup.first().map_or(0, |row| row.len());
+        let height = soup.len() + 2 * self.margin;
+        let width = soup_width + 2 * self.margin;
+        if height == 0 || width == 0 || soup.iter().any(|row| row.len() != soup_width) {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        let mut census = Census {
+            soups: 1,
+            ..Census::default()
+        };
+        let mut world = vec![vec![false; width]; height];
+        for (i, row) in soup.iter().enumerate() {
+            world[self.margin + i][self.margin..self.margin + row.len()].copy_from_slice(row);
+        }
+
+        let mut ca = self.automaton(world);
+        // Recent worlds with their hashes, compared in full on a hash match
+        let start = ca.world();
+        let mut recent = VecDeque::from([(world_hash(&start), start)]);
+        let mut period = 1;
+
+        for _ in 0..self.max_generations {
+            ca.step();
+            let mut world = ca.world();
+            if self.remove_escaping(&mut world, &mut census) {
+                ca = self.automaton(world.clone());
+            }
+
+            let hash = world_hash(&world);
+            if let Some(back) = recent
+                .iter()
+                .rev()
+                .position(|(h, previous)| *h == hash && *previous == world)
+            {
+                period = back + 1;
+                break;
+            }
+            recent.push_back((hash, world));
+            if recent.len() > self.max_period {
+                recent.pop_front();
+            }
+        }
+
+        // Group over every phase of the ash, so objects that only come close
+        // in some phases are still counted together
+        let ash = ca.world();
+        let mut envelope = ash.clone();
+        for _ in 1..period {
+            ca.step();
+            for (row, next) in envelope.iter_mut().zip(ca.world()) {
+                for (cell, alive) in row.iter_mut().zip(next) {
+                    *cell |= alive;
+                }
+            }
+        }
+
+        for component in components(&envelope, NeighborhoodShape::Moore, GAP, false) {
+            if let Some(pattern) = component_pattern(&ash, &component) {
+                census.record(apgcode(&pattern, &self.rule, self.max_period));
+            }
+        }
+
+        Ok(census)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn life() -> LifeLikeRule {
+        LifeLikeRule::parse("B3/S23").expect("Parsing failed")
+    }
+
+    fn place(world: &mut [Vec<bool>], rows: &[&str], (top, left): (usize, usize)) {
+        for (i, row) in rows.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                world[top + i][left + j] = c == 'o';
+            }
+        }
+    }
+
+    #[test]
+    fn test_census_soup() {
+        let mut soup = vec![vec![false; 20]; 20];
+        place(&mut soup, &["oo", "oo"], (2, 2));
+        place(&mut soup, &["ooo"], (2, 12));
+        place(&mut soup, &[".o.", "..o", "ooo"], (12, 12));
+        // Two blocks far enough apart not to be one object
+        place(&mut soup, &["oo.....oo", "oo.....oo"], (12, 1));
+
+        let census = SoupSearch::new(life(), 0)
+            .with_margin(8)
+            .census_soup(&soup)
+            .expect("Invalid soup");
+
+        assert_eq!(census.soups(), 1);
+        assert_eq!(census.count("xs4_33"), 3);
+        assert_eq!(census.count("xp2_7"), 1);
+        // The glider flies off and is counted on its way out
+        assert_eq!(census.count("xq4_153"), 1);
+        assert_eq!(census.total(), 5);
+    }
+
+    #[test]
+    fn test_small_worlds() {
+        let mut soup = vec![vec![false; 3]; 3];
+        place(&mut soup, &["ooo"], (1, 0));
+
+        // Without a margin the whole world is within the edge, so the
+        // blinker is counted as escaping straight away
+        let census = SoupSearch::new(life(), 0)
+            .with_margin(0)
+            .census_soup(&soup)
+            .expect("Invalid soup");
+        assert_eq!(census.count("xp2_7"), 1);
+        assert_eq!(census.total(), 1);
+    }
+
+    #[test]
+    fn test_invalid_soups() {
+        let search = SoupSearch::new(life(), 0).with_margin(0);
+        assert!(search.census_soup(&[]).is_err());
+        assert!(search
+            .census_soup(&[vec![true, false], vec![true]])
+            .is_err());
+        assert!(search.clone().with_soup_size(0).run(2).is_err());
+
+        // An empty soup with room around it is just an empty world
+        let census = SoupSearch::new(life(), 0)
+            .with_soup_size(0)
+            .run(2)
+            .expect("Invalid soup");
+        assert_eq!((census.soups(), census.total()), (2, 0));
+    }
+
+    #[test]
+    fn test_invalid_density() {
+        for density in [-0.5, 1.5, f64::NAN] {
+            assert!(SoupSearch::new(life(), 0).with_density(density).is_err());
+        }
+
+        let full = SoupSearch::new(life(), 0)
+            .with_soup_size(4)
+            .with_density(1.0)
+            .expect("Invalid density");
+        assert_eq!(full.soup(0), vec![vec![true; 4]; 4]);
+    }
+
+    #[test]
+    fn test_search_is_reproducible() {
+        let search = SoupSearch::new(life(), 42)
+            .with_soup_size(8)
+            .with_margin(16);
+
+        assert_eq!(search.soup(3), search.soup(3));
+        assert_ne!(search.soup(3), search.soup(4));
+        assert_ne!(
+            search.soup(3),
+            SoupSearch::new(life(), 43).with_soup_size(8).soup(3)
+        );
+
+        let census = search.run(6).expect("Invalid soup");
+        assert_eq!(census.soups(), 6);
+        assert!(census.total() > 0);
+        assert_eq!(
+            search.clone().with_threads(4).run(6).expect("Invalid soup"),
+            census
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let mut census = Census::default();
+        for code in ["xp2_7", "xs4_33", "xs6_696", "xs4_33"] {
+            census.record(code.into());
+        }
+        census.soups = 2;
+
+        assert_eq!(
+            census.table(),
+            vec![("xs4_33", 2), ("xp2_7", 1), ("xs6_696", 1)]
+        );
+        assert_eq!(
+            census.to_string(),
+            "2 soups, 4 objects\nxs4_33  2\nxp2_7   1\nxs6_696 1\n"
+        );
+
+        let mut merged = census.clone();
+        merged.merge(&census);
+        assert_eq!(merged.soups(), 4);
+        assert_eq!(merged.count("xs4_33"), 4);
+        assert_eq!(merged.count("xq4_153"), 0);
+    }
+}
@@ -0,0 +1,521 @@
+use std::fmt;
+
+use crate::automaton::CellularAutomaton;
+
+// The shortest run of indices covering every occupied one, as its start and
+// length. With wrapping the run may cross the end and start again at 0.
+pub(crate) fn occupied_span(occupied: &[bool], wrapping: bool) -> Option<(usize, usize)> {
+    let size = occupied.len();
+    let first = occupied.iter().position(|&o| o)?;
+    let last = occupied.iter().rposition(|&o| o)?;
+
+    if !wrapping {
+        return Some((first, last - first + 1));
+    }
+
+    // Cut the ring at its longest gap; the run starts just after it
+    let mut longest_gap = 0;
+    let mut start = first;
+    let mut previous = last;
+    for k in (0..size).filter(|&k| occupied[k]) {
+        let gap = (k + size - previous - 1) % size;
+        if gap > longest_gap {
+            longest_gap = gap;
+            start = k;
+        }
+        previous = k;
+    }
+
+    Some((start, size - longest_gap))
+}
+
+// The live cells of a 2D world, those not in the default state, cropped to
+// their bounding box. Two worlds holding the same object in different
+// places give equal patterns.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pattern<T> {
+    cells: Vec<Vec<T>>,
+}
+
+impl<T: Copy + Default + PartialEq> Pattern<T> {
+    // The pattern in `world` and the position of its top-left corner, or
+    // `None` if nothing is alive. On a torus the box may wrap round the edge.
+    pub fn from_world(world: &[Vec<T>], wrapping: bool) -> Option<(Self, (usize, usize))> {
+        let height = world.len();
+        let width = world.first().map_or(0, |row| row.len());
+        let background = T::default();
+
+        let rows: Vec<bool> = world
+            .iter()
+            .map(|row| row.iter().any(|&cell| cell != background))
+            .collect();
+        let columns: Vec<bool> = (0..width)
+            .map(|j| world.iter().any(|row| row[j] != background))
+            .collect();
+
+        let (top, rows) = occupied_span(&rows, wrapping)?;
+        let (left, columns) = occupied_span(&columns, wrapping)?;
+
+        let cells = (0..rows)
+            .map(|i| {
+                (0..columns)
+                    .map(|j| world[(top + i) % height][(left + j) % width])
+                    .collect()
+            })
+            .collect();
+
+        Some((Self { cells }, (top, left)))
+    }
+
+    pub fn cells(&self) -> &Vec<Vec<T>> {
+        &self.cells
+    }
+
+    pub fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    pub fn population(&self) -> usize {
+        let background = T::default();
+        self.cells
+            .iter()
+            .flatten()
+            .filter(|&&cell| cell != background)
+            .count()
+    }
+
+    // Mirrored left to right if `reflect`, then turned clockwise by
+    // `rotations` quarter turns
+    pub fn transformed(&self, rotations: usize, reflect: bool) -> Self {
+        let mut cells = self.cells.clone();
+        if reflect {
+            for row in cells.iter_mut() {
+                row.reverse();
+            }
+        }
+
+        for _ in 0..rotations % 4 {
+            let height = cells.len();
+            let width = cells[0].len();
+            cells = (0..width)
+                .map(|i| (0..height).map(|j| cells[height - 1 - j][i]).collect())
+                .collect();
+        }
+
+        Self { cells }
+    }
+}
+
+impl<T: Copy + Default + Ord> Pattern<T> {
+    // The same representative for all eight rotations and reflections of the
+    // pattern, for telling objects apart whatever their orientation
+    pub fn canonical(&self) -> Self {
+        (0..4)
+            .flat_map(|rotations| [false, true].map(|reflect| self.transformed(rotations, reflect)))
+            .min()
+            .expect("There are always eight transformations")
+    }
+}
+
+// A speed as a reduced fraction of c, one cell per generation. Distances
+// are counted in king moves, so a diagonal step is one cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Speed {
+    pub cells: usize,
+    pub generations: usize,
+}
+
+impl Speed {
+    pub fn new(cells: usize, generations: usize) -> Self {
+        let (mut a, mut b) = (cells, generations);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        let divisor = a.max(1);
+
+        Self {
+            cells: cells / divisor,
+            generations: generations / divisor,
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.cells as f64 / self.generations as f64
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.cells, self.generations) {
+            (0, _) => write!(f, "0"),
+            (1, 1) => write!(f, "c"),
+            (1, generations) => write!(f, "c/{}", generations),
+            (cells, 1) => write!(f, "{}c", cells),
+            (cells, generations) => write!(f, "{}c/{}", cells, generations),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Classification {
+    // Nothing alive, now or at some point within the periods tried
+    Empty,
+    StillLife,
+    Oscillator { period: usize },
+    // Moves `dx` columns east and `dy` rows south every `period` generations
+    Spaceship { period: usize, dx: isize, dy: isize },
+    // Did not come back to its starting shape within the periods tried
+    Unknown,
+}
+
+impl Classification {
+    // `None` unless the pattern is known to repeat
+    pub fn speed(&self) -> Option<Speed> {
+        match *self {
+            Classification::StillLife => Some(Speed::new(0, 1)),
+            Classification::Oscillator { period } => Some(Speed::new(0, period)),
+            Classification::Spaceship { period, dx, dy } => {
+                Some(Speed::new(dx.unsigned_abs().max(dy.unsigned_abs()), period))
+            }
+            Classification::Empty | Classification::Unknown => None,
+        }
+    }
+}
+
+// Movement from one position to another along an axis of `size` cells. On a
+// torus this is the shorter way round, eastwards or southwards on a tie.
+fn displacement(from: usize, to: usize, size: usize, wrapping: bool) -> isize {
+    let d = to as isize - from as isize;
+    if !wrapping {
+        return d;
+    }
+
+    let size = size as isize;
+    let d = d.rem_euclid(size);
+    if d > size / 2 {
+        d - size
+    } else {
+        d
+    }
+}
+
+// What `classify` reports for a pattern that came back to its starting shape
+// at `period`, with its corner moved from `from` to `to`
+fn repeated(
+    period: usize,
+    from: (usize, usize),
+    to: (usize, usize),
+    (height, width): (usize, usize),
+    wrapping: bool,
+) -> Classification {
+    let dx = displacement(from.1, to.1, width, wrapping);
+    let dy = displacement(from.0, to.0, height, wrapping);
+
+    match (dx, dy, period) {
+        (0, 0, 1) => Classification::StillLife,
+        (0, 0, _) => Classification::Oscillator { period },
+        _ => Classification::Spaceship { period, dx, dy },
+    }
+}
+
+// Steps `ca` until its pattern comes back to the current shape, up to
+// `max_period` generations. Anything the pattern sheds or grows counts, so
+// classify a single object rather than a whole soup.
+pub fn classify<T, C>(ca: &mut C, wrapping: bool, max_period: usize) -> Classification
+where
+    T: Copy + Default + PartialEq,
+    C: CellularAutomaton<WorldType = Vec<Vec<T>>>,
+{
+    let world = ca.world();
+    let size = (world.len(), world.first().map_or(0, |row| row.len()));
+
+    let Some((pattern, corner)) = Pattern::from_world(&world, wrapping) else {
+        return Classification::Empty;
+    };
+
+    for period in 1..=max_period {
+        ca.step();
+        let Some((next, n_corner)) = Pattern::from_world(&ca.world(), wrapping) else {
+            return Classification::Empty;
+        };
+
+        if next == pattern {
+            return repeated(period, corner, n_corner, size, wrapping);
+        }
+    }
+
+    Classification::Unknown
+}
+
+// A pattern that first comes back rotated or mirrored, as in
+// `Pattern::transformed`, after `period` generations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symmetry {
+    pub rotations: usize,
+    pub reflect: bool,
+    pub period: usize,
+}
+
+// Like `classify`, but each generation is also compared against the seven
+// other rotations and reflections of the starting pattern, so that flipping
+// oscillators and glide-reflective spaceships are found by their half (or
+// quarter) period within `max_period`. The classification is still of the
+// full period, stepped on to once a symmetry is found; the symmetry is
+// `None` if the pattern came back unturned first.
+pub fn classify_with_symmetry<T, C>(
+    ca: &mut C,
+    wrapping: bool,
+    max_period: usize,
+) -> (Classification, Option<Symmetry>)
+where
+    T: Copy + Default + PartialEq,
+    C: CellularAutomaton<WorldType = Vec<Vec<T>>>,
+{
+    let world = ca.world();
+    let size = (world.len(), world.first().map_or(0, |row| row.len()));
+
+    let Some((pattern, corner)) = Pattern::from_world(&world, wrapping) else {
+        return (Classification::Empty, None);
+    };
+    let transforms: Vec<(usize, bool, Pattern<T>)> = (0..4)
+        .flat_map(|rotations| [false, true].map(|reflect| (rotations, reflect)))
+        .skip(1)
+        .map(|(rotations, reflect)| (rotations, reflect, pattern.transformed(rotations, reflect)))
+        .collect();
+
+    let mut symmetry: Option<Symmetry> = None;
+    let mut period = 0;
+    // Every rotation and reflection comes back to the identity within four
+    // applications
+    while period < symmetry.map_or(max_period, |s| max_period.max(4 * s.period)) {
+        ca.step();
+        period += 1;
+        let Some((next, n_corner)) = Pattern::from_world(&ca.world(), wrapping) else {
+            return (Classification::Empty, symmetry);
+        };
+
+        if next == pattern {
+            return (repeated(period, corner, n_corner, size, wrapping), symmetry);
+        }
+        if symmetry.is_none() {
+            symmetry = transforms
+                .iter()
+                .find(|(_, _, transformed)| *transformed == next)
+                .map(|&(rotations, reflect, _)| Symmetry {
+                    rotations,
+                    reflect,
+                    period,
+                });
+        }
+    }
+
+    (Classification::Unknown, symmetry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conway::ConwayCellularAutomaton;
+
+    const GLIDER: [(usize, usize); 5] = [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)];
+
+    fn world_with(
+        height: usize,
+        width: usize,
+        cells: &[(usize, usize)],
+        (top, left): (usize, usize),
+    ) -> Vec<Vec<bool>> {
+        let mut world = vec![vec![false; width]; height];
+        for &(i, j) in cells {
+            world[(top + i) % height][(left + j) % width] = true;
+        }
+        world
+    }
+
+    fn classify_conway(world: Vec<Vec<bool>>, wrapping: bool) -> Classification {
+        let mut ca = ConwayCellularAutomaton(world, wrapping).expect("Construction failed");
+        classify(&mut ca, wrapping, 30)
+    }
+
+    #[test]
+    fn test_still_life_and_oscillator() {
+        let block = world_with(6, 6, &[(0, 0), (0, 1), (1, 0), (1, 1)], (2, 2));
+        assert_eq!(classify_conway(block, false), Classification::StillLife);
+
+        let blinker = world_with(7, 7, &[(0, 0), (0, 1), (0, 2)], (3, 2));
+        let class = classify_conway(blinker, true);
+        assert_eq!(class, Classification::Oscillator { period: 2 });
+        assert_eq!(class.speed(), Some(Speed::new(0, 1)));
+    }
+
+    #[test]
+    fn test_glider() {
+        let class = classify_conway(world_with(12, 12, &GLIDER, (4, 4)), true);
+
+        assert_eq!(
+            class,
+            Classification::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: 1
+            }
+        );
+        let speed = class.speed().expect("Spaceships have a speed");
+        assert_eq!(speed.to_string(), "c/4");
+        assert_eq!(speed.as_f64(), 0.25);
+    }
+
+    #[test]
+    fn test_glider_across_the_edge() {
+        // Straddling the corner of the torus makes no difference
+        let world = world_with(10, 10, &GLIDER, (9, 8));
+        let (pattern, corner) = Pattern::from_world(&world, true).expect("Nothing alive");
+
+        assert_eq!(corner, (9, 8));
+        assert_eq!(
+            pattern,
+            Pattern::from_world(&world_with(5, 5, &GLIDER, (0, 0)), false)
+                .expect("Nothing alive")
+                .0
+        );
+        assert_eq!(
+            classify_conway(world, true),
+            Classification::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_lightweight_spaceship() {
+        let lwss = [
+            (0, 1),
+            (0, 4),
+            (1, 0),
+            (2, 0),
+            (2, 4),
+            (3, 0),
+            (3, 1),
+            (3, 2),
+            (3, 3),
+        ];
+        let class = classify_conway(world_with(16, 16, &lwss, (6, 8)), true);
+
+        assert_eq!(
+            class,
+            Classification::Spaceship {
+                period: 4,
+                dx: -2,
+                dy: 0
+            }
+        );
+        assert_eq!(class.speed().map(|s| s.to_string()), Some("c/2".into()));
+    }
+
+    #[test]
+    fn test_symmetry() {
+        // The glider is its own mirror image two generations on, so it is
+        // found with half its period
+        let mut ca = ConwayCellularAutomaton(world_with(12, 12, &GLIDER, (4, 4)), true)
+            .expect("Construction failed");
+        let (class, symmetry) = classify_with_symmetry(&mut ca, true, 2);
+        assert_eq!(
+            class,
+            Classification::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: 1
+            }
+        );
+        let symmetry = symmetry.expect("The glider is glide-reflective");
+        assert_eq!(symmetry.period, 2);
+        assert!(symmetry.reflect);
+
+        let mut ca = ConwayCellularAutomaton(world_with(12, 12, &GLIDER, (4, 4)), true)
+            .expect("Construction failed");
+        assert_eq!(classify(&mut ca, true, 2), Classification::Unknown);
+
+        // The blinker turns a quarter each generation
+        let mut ca =
+            ConwayCellularAutomaton(world_with(7, 7, &[(0, 0), (0, 1), (0, 2)], (3, 2)), false)
+                .expect("Construction failed");
+        let (class, symmetry) = classify_with_symmetry(&mut ca, false, 1);
+        assert_eq!(class, Classification::Oscillator { period: 2 });
+        let symmetry = symmetry.expect("The blinker flips");
+        assert_eq!((symmetry.period, symmetry.rotations % 2), (1, 1));
+
+        let block = world_with(6, 6, &[(0, 0), (0, 1), (1, 0), (1, 1)], (2, 2));
+        let mut ca = ConwayCellularAutomaton(block, false).expect("Construction failed");
+        assert_eq!(
+            classify_with_symmetry(&mut ca, false, 10),
+            (Classification::StillLife, None)
+        );
+    }
+
+    #[test]
+    fn test_empty_and_unknown() {
+        assert_eq!(
+            classify_conway(vec![vec![false; 5]; 5], true),
+            Classification::Empty
+        );
+
+        // A lone cell dies at once
+        assert_eq!(
+            classify_conway(world_with(5, 5, &[(0, 0)], (2, 2)), true),
+            Classification::Empty
+        );
+
+        // The R-pentomino takes over a thousand generations to settle
+        let r_pentomino = [(0, 1), (0, 2), (1, 0), (1, 1), (2, 1)];
+        let class = classify_conway(world_with(40, 40, &r_pentomino, (18, 18)), true);
+        assert_eq!(class, Classification::Unknown);
+        assert_eq!(class.speed(), None);
+    }
+
+    #[test]
+    fn test_canonical() {
+        let (glider, _) =
+            Pattern::from_world(&world_with(3, 3, &GLIDER, (0, 0)), false).expect("Nothing alive");
+
+        assert_eq!(glider.population(), 5);
+        assert_eq!((glider.height(), glider.width()), (3, 3));
+
+        let canonical = glider.canonical();
+        for rotations in 0..4 {
+            for reflect in [false, true] {
+                assert_eq!(
+                    glider.transformed(rotations, reflect).canonical(),
+                    canonical
+                );
+            }
+        }
+
+        let (l, _) = Pattern::from_world(
+            &world_with(3, 2, &[(0, 0), (1, 0), (2, 0), (2, 1)], (0, 0)),
+            false,
+        )
+        .expect("Nothing alive");
+        let turned = l.transformed(1, false);
+        assert_eq!(
+            turned.cells(),
+            &vec![vec![true, true, true], vec![true, false, false]]
+        );
+        assert_ne!(turned, l);
+        assert_eq!(turned.canonical(), l.canonical());
+    }
+
+    #[test]
+    fn test_speed_display() {
+        assert_eq!(Speed::new(2, 4).to_string(), "c/2");
+        assert_eq!(Speed::new(2, 5).to_string(), "2c/5");
+        assert_eq!(Speed::new(3, 3).to_string(), "c");
+        assert_eq!(Speed::new(0, 2).to_string(), "0");
+    }
+}
@@ -0,0 +1,301 @@
+use crate::automaton::NeighborhoodShape;
+use crate::classify::occupied_span;
+
+// One connected group of live cells, those not in the default state
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component<T> {
+    cells: Vec<(usize, usize)>,
+    top: usize,
+    left: usize,
+    world: Vec<Vec<T>>,
+    world_size: (usize, usize),
+    centroid: (f64, f64),
+}
+
+impl<T: Copy + Default> Component<T> {
+    fn new(source: &[Vec<T>], cells: Vec<(usize, usize)>, wrapping: bool) -> Self {
+        let height = source.len();
+        let width = source[0].len();
+
+        let mut rows = vec![false; height];
+        let mut columns = vec![false; width];
+        for &(i, j) in &cells {
+            rows[i] = true;
+            columns[j] = true;
+        }
+        let (top, box_height) = occupied_span(&rows, wrapping).expect("Components are not empty");
+        let (left, box_width) =
+            occupied_span(&columns, wrapping).expect("Components are not empty");
+
+        // Average in coordinates that run on past the edge of a torus, so a
+        // component wrapping round gets its centroid in the right place
+        let (sum_i, sum_j) = cells.iter().fold((0.0, 0.0), |(sum_i, sum_j), &(i, j)| {
+            (
+                sum_i + (top + (i + height - top) % height) as f64,
+                sum_j + (left + (j + width - left) % width) as f64,
+            )
+        });
+        let count = cells.len() as f64;
+        let centroid = (
+            (sum_i / count).rem_euclid(height as f64),
+            (sum_j / count).rem_euclid(width as f64),
+        );
+
+        let mut component = Self {
+            cells,
+            top,
+            left,
+            world: vec![vec![T::default(); box_width]; box_height],
+            world_size: (height, width),
+            centroid,
+        };
+        component.world = component.extract(source);
+        component
+    }
+
+    // Positions of the live cells, in row-major order
+    pub fn cells(&self) -> &Vec<(usize, usize)> {
+        &self.cells
+    }
+
+    // Top row, left column, height and width. On a torus the box may run
+    // past the bottom or right edge and continue at the top or left.
+    pub fn bounding_box(&self) -> (usize, usize, usize, usize) {
+        (self.top, self.left, self.world.len(), self.world[0].len())
+    }
+
+    pub fn population(&self) -> usize {
+        self.cells.len()
+    }
+
+    // Mean row and column of the live cells
+    pub fn centroid(&self) -> (f64, f64) {
+        self.centroid
+    }
+
+    // The component on its own, cropped to its bounding box
+    pub fn world(&self) -> &Vec<Vec<T>> {
+        &self.world
+    }
+
+    // The component's cells as they are in `world`, cropped to its bounding
+    // box. With a later phase of the world this follows an oscillator or
+    // another object through its envelope.
+    pub fn extract(&self, world: &[Vec<T>]) -> Vec<Vec<T>> {
+        let (height, width) = self.world_size;
+        let mut extracted = vec![vec![T::default(); self.world[0].len()]; self.world.len()];
+
+        for &(i, j) in &self.cells {
+            extracted[(i + height - self.top) % height][(j + width - self.left) % width] =
+                world[i][j];
+        }
+        extracted
+    }
+}
+
+// Offsets of the cells that join a cell's component: up to `gap` empty cells
+// may lie between two cells of one component, counted in king moves for
+// Moore and in rook moves for von Neumann adjacency
+fn reach(shape: NeighborhoodShape, gap: usize) -> Vec<(isize, isize)> {
+    let range = gap as isize + 1;
+
+    (-range..=range)
+        .flat_map(|di| (-range..=range).map(move |dj| (di, dj)))
+        .filter(|&(di, dj)| {
+            (di, dj) != (0, 0)
+                && match shape {
+                    NeighborhoodShape::Moore => true,
+                    NeighborhoodShape::VonNeumann => di.abs() + dj.abs() <= range,
+                }
+        })
+        .collect()
+}
+
+// Labels every live cell with the index of its component, numbered in
+// row-major order of their first cells; dead cells get `None`
+pub fn label_components<T: Copy + Default + PartialEq>(
+    world: &[Vec<T>],
+    shape: NeighborhoodShape,
+    gap: usize,
+    wrapping: bool,
+) -> Vec<Vec<Option<usize>>> {
+    let height = world.len();
+    let width = world.first().map_or(0, |row| row.len());
+    let background = T::default();
+    let offsets = reach(shape, gap);
+
+    let mut labels = vec![vec![None; width]; height];
+    let mut count = 0;
+    let mut queue = Vec::new();
+
+    for i in 0..height {
+        for j in 0..width {
+            if world[i][j] == background || labels[i][j].is_some() {
+                continue;
+            }
+
+            labels[i][j] = Some(count);
+            queue.push((i, j));
+            while let Some((c_i, c_j)) = queue.pop() {
+                for &(di, dj) in &offsets {
+                    let (n_i, n_j) = (c_i as isize + di, c_j as isize + dj);
+                    let (n_i, n_j) = if wrapping {
+                        (
+                            n_i.rem_euclid(height as isize) as usize,
+                            n_j.rem_euclid(width as isize) as usize,
+                        )
+                    } else if n_i >= 0 && n_i < height as isize && n_j >= 0 && n_j < width as isize
+                    {
+                        (n_i as usize, n_j as usize)
+                    } else {
+                        continue;
+                    };
+
+                    if world[n_i][n_j] != background && labels[n_i][n_j].is_none() {
+                        labels[n_i][n_j] = Some(count);
+                        queue.push((n_i, n_j));
+                    }
+                }
+            }
+            count += 1;
+        }
+    }
+
+    labels
+}
+
+// The separate objects of a world, in the order of `label_components`
+pub fn components<T: Copy + Default + PartialEq>(
+    world: &[Vec<T>],
+    shape: NeighborhoodShape,
+    gap: usize,
+    wrapping: bool,
+) -> Vec<Component<T>> {
+    let labels = label_components(world, shape, gap, wrapping);
+
+    let mut cells: Vec<Vec<(usize, usize)>> = Vec::new();
+    for (i, row) in labels.iter().enumerate() {
+        for (j, label) in row.iter().enumerate() {
+            if let &Some(label) = label {
+                if label == cells.len() {
+                    cells.push(Vec::new());
+                }
+                cells[label].push((i, j));
+            }
+        }
+    }
+
+    cells
+        .into_iter()
+        .map(|cells| Component::new(world, cells, wrapping))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads rows of `.` and `o`
+    fn world(rows: &[&str]) -> Vec<Vec<bool>> {
+        rows.iter()
+            .map(|row| row.chars().map(|c| c == 'o').collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_gap() {
+        let blocks = world(&["oo.oo....", "oo.oo...o"]);
+
+        let touching = components(&blocks, NeighborhoodShape::Moore, 0, false);
+        assert_eq!(touching.len(), 3);
+        assert_eq!(
+            touching.iter().map(|c| c.population()).collect::<Vec<_>>(),
+            vec![4, 4, 1]
+        );
+
+        let one_gap = components(&blocks, NeighborhoodShape::Moore, 1, false);
+        assert_eq!(one_gap.len(), 2);
+        assert_eq!(one_gap[0].bounding_box(), (0, 0, 2, 5));
+
+        assert_eq!(
+            components(&blocks, NeighborhoodShape::Moore, 3, false).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_shape() {
+        let diagonal = world(&["o..", ".o.", "..o"]);
+
+        assert_eq!(
+            components(&diagonal, NeighborhoodShape::Moore, 0, false).len(),
+            1
+        );
+        assert_eq!(
+            components(&diagonal, NeighborhoodShape::VonNeumann, 0, false).len(),
+            3
+        );
+        assert_eq!(
+            components(&diagonal, NeighborhoodShape::VonNeumann, 1, false).len(),
+            1
+        );
+
+        assert_eq!(
+            label_components(&diagonal, NeighborhoodShape::VonNeumann, 0, false),
+            vec![
+                vec![Some(0), None, None],
+                vec![None, Some(1), None],
+                vec![None, None, Some(2)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_glider() {
+        let mut glider = vec![vec![false; 8]; 8];
+        for (i, j) in [(2, 3), (3, 4), (4, 2), (4, 3), (4, 4)] {
+            glider[i][j] = true;
+        }
+
+        let found = components(&glider, NeighborhoodShape::Moore, 0, false);
+        assert_eq!(found.len(), 1);
+
+        let glider = &found[0];
+        assert_eq!(glider.population(), 5);
+        assert_eq!(glider.bounding_box(), (2, 2, 3, 3));
+        assert_eq!(glider.centroid(), (3.4, 3.2));
+        assert_eq!(glider.cells()[0], (2, 3));
+        assert_eq!(glider.world(), &world(&[".o.", "..o", "ooo"]));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        // A block split over the four corners of a torus
+        let corners = world(&["o...o", ".....", ".....", "o...o"]);
+
+        assert_eq!(
+            components(&corners, NeighborhoodShape::Moore, 0, false).len(),
+            4
+        );
+
+        let found = components(&corners, NeighborhoodShape::Moore, 0, true);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].bounding_box(), (3, 4, 2, 2));
+        assert_eq!(found[0].world(), &world(&["oo", "oo"]));
+        assert_eq!(found[0].centroid(), (3.5, 4.5));
+    }
+
+    #[test]
+    fn test_multi_state() {
+        let states = vec![vec![0u8, 2, 0, 0], vec![0, 1, 0, 3]];
+        let found = components(&states, NeighborhoodShape::VonNeumann, 0, false);
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].world(), &vec![vec![2], vec![1]]);
+        assert_eq!(found[1].world(), &vec![vec![3]]);
+
+        // Following the first object into another world keeps its shape
+        let later = vec![vec![0u8, 1, 0, 0], vec![0, 2, 0, 0]];
+        assert_eq!(found[0].extract(&later), vec![vec![1], vec![2]]);
+    }
+}
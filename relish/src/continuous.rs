@@ -0,0 +1,549 @@
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidKernelError;
+
+// The profile of a single kernel shell over its normalised radius `0..1`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KernelCore {
+    // exp(alpha - alpha / (4r(1 - r))), the usual Lenia shell for alpha = 4
+    Exponential(f64),
+    // (4r(1 - r))^alpha
+    Polynomial(i32),
+    // 1 on the middle half of the shell, 0 elsewhere
+    Step,
+}
+
+impl KernelCore {
+    fn value(&self, r: f64) -> f64 {
+        if r <= 0.0 || r >= 1.0 {
+            return 0.0;
+        }
+
+        match *self {
+            KernelCore::Exponential(alpha) => (alpha - alpha / (4.0 * r * (1.0 - r))).exp(),
+            KernelCore::Polynomial(alpha) => (4.0 * r * (1.0 - r)).powi(alpha),
+            KernelCore::Step => {
+                if (0.25..=0.75).contains(&r) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+// A square, odd-sized convolution kernel normalised to sum to 1. The weight
+// at `weights()[radius + dy][radius + dx]` applies to the cell `dy` rows
+// below and `dx` columns right of the one being updated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    radius: usize,
+    weights: Vec<Vec<f64>>,
+}
+
+impl Kernel {
+    pub fn new(weights: Vec<Vec<f64>>) -> Result<Self, InvalidKernelError> {
+        let size = weights.len();
+        if size % 2 != 1 || weights.iter().any(|row| row.len() != size) {
+            return Err(InvalidKernelError);
+        }
+
+        let total: f64 = weights.iter().flatten().sum();
+        if !total.is_finite() || total <= 0.0 {
+            return Err(InvalidKernelError);
+        }
+
+        Ok(Self {
+            radius: size / 2,
+            weights: weights
+                .into_iter()
+                .map(|row| row.into_iter().map(|w| w / total).collect())
+                .collect(),
+        })
+    }
+
+    // A radially symmetric kernel made of concentric shells, one per entry of
+    // `peaks`, each following `core` and scaled by its peak height
+    pub fn lenia(
+        radius: usize,
+        peaks: &[f64],
+        core: KernelCore,
+    ) -> Result<Self, InvalidKernelError> {
+        if radius == 0 || peaks.is_empty() {
+            return Err(InvalidKernelError);
+        }
+
+        let size = 2 * radius + 1;
+        let weights = (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| {
+                        let dy = i as f64 - radius as f64;
+                        let dx = j as f64 - radius as f64;
+                        let r = (dy * dy + dx * dx).sqrt() / radius as f64 * peaks.len() as f64;
+                        let shell = r.floor() as usize;
+                        match peaks.get(shell) {
+                            Some(peak) => peak * core.value(r.fract()),
+                            None => 0.0,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self::new(weights)
+    }
+
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    pub fn weights(&self) -> &Vec<Vec<f64>> {
+        &self.weights
+    }
+}
+
+// Gaussian bump rescaled to -1..1, peaking at `mu`
+pub fn gaussian_growth(mu: f64, sigma: f64) -> impl Fn(f64) -> f64 {
+    move |u| 2.0 * (-(u - mu).powi(2) / (2.0 * sigma * sigma)).exp() - 1.0
+}
+
+// Compactly supported polynomial bump, zero growth beyond 3 sigma of `mu`
+pub fn polynomial_growth(mu: f64, sigma: f64) -> impl Fn(f64) -> f64 {
+    move |u| {
+        2.0 * (1.0 - (u - mu).powi(2) / (9.0 * sigma * sigma))
+            .max(0.0)
+            .powi(4)
+            - 1.0
+    }
+}
+
+pub fn step_growth(mu: f64, sigma: f64) -> impl Fn(f64) -> f64 {
+    move |u| if (u - mu).abs() <= sigma { 1.0 } else { -1.0 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convolution {
+    Direct,
+    Fft,
+}
+
+// Kernels wider than this are convolved through FFT by default
+const DIRECT_CONVOLUTION_MAX_RADIUS: usize = 4;
+
+struct FftConvolution {
+    row_forward: Arc<dyn Fft<f64>>,
+    row_inverse: Arc<dyn Fft<f64>>,
+    col_forward: Arc<dyn Fft<f64>>,
+    col_inverse: Arc<dyn Fft<f64>>,
+    // Kernel spectrum, stored column-major like the intermediate buffers
+    spectrum: Vec<Complex<f64>>,
+}
+
+fn transpose(buffer: &[Complex<f64>], height: usize, width: usize) -> Vec<Complex<f64>> {
+    let mut transposed = vec![Complex::new(0.0, 0.0); buffer.len()];
+    for i in 0..height {
+        for j in 0..width {
+            transposed[j * height + i] = buffer[i * width + j];
+        }
+    }
+    transposed
+}
+
+impl FftConvolution {
+    fn new(kernel: &Kernel, height: usize, width: usize) -> Self {
+        let mut planner = FftPlanner::new();
+        let mut convolution = Self {
+            row_forward: planner.plan_fft_forward(width),
+            row_inverse: planner.plan_fft_inverse(width),
+            col_forward: planner.plan_fft_forward(height),
+            col_inverse: planner.plan_fft_inverse(height),
+            spectrum: Vec::new(),
+        };
+
+        // Flip the kernel around the origin of the torus so that the
+        // circular convolution gives the same weighted sum as the direct one
+        let radius = kernel.radius as isize;
+        let mut flipped = vec![Complex::new(0.0, 0.0); height * width];
+        for (i, row) in kernel.weights.iter().enumerate() {
+            for (j, &w) in row.iter().enumerate() {
+                let r = (radius - i as isize).rem_euclid(height as isize) as usize;
+                let c = (radius - j as isize).rem_euclid(width as isize) as usize;
+                flipped[r * width + c] += w;
+            }
+        }
+        convolution.spectrum = convolution.forward(flipped, height, width);
+        convolution
+    }
+
+    fn forward(
+        &self,
+        mut buffer: Vec<Complex<f64>>,
+        height: usize,
+        width: usize,
+    ) -> Vec<Complex<f64>> {
+        self.row_forward.process(&mut buffer);
+        let mut buffer = transpose(&buffer, height, width);
+        self.col_forward.process(&mut buffer);
+        buffer
+    }
+
+    fn convolve(&self, world: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let height = world.len();
+        let width = world[0].len();
+
+        let buffer = world
+            .iter()
+            .flatten()
+            .map(|&v| Complex::new(v, 0.0))
+            .collect();
+        let mut buffer: Vec<Complex<f64>> = self
+            .forward(buffer, height, width)
+            .into_iter()
+            .zip(&self.spectrum)
+            .map(|(a, k)| a * k)
+            .collect();
+
+        self.col_inverse.process(&mut buffer);
+        let mut buffer = transpose(&buffer, width, height);
+        self.row_inverse.process(&mut buffer);
+
+        let scale = (height * width) as f64;
+        buffer
+            .chunks(width)
+            .map(|row| row.iter().map(|c| c.re / scale).collect())
+            .collect()
+    }
+}
+
+// A continuous-state automaton in the style of Lenia: every step convolves
+// the world with a kernel on a torus to get the potential `U`, then moves
+// each cell by `dt * growth(U)`, clamped to `0..=1`.
+pub struct ContinuousCellularAutomaton {
+    world: Vec<Vec<f64>>,
+    generation: usize,
+    kernel: Kernel,
+    growth_fn: Box<dyn Fn(f64) -> f64>,
+    dt: f64,
+    fft: Option<FftConvolution>,
+}
+
+impl ContinuousCellularAutomaton {
+    pub fn new(
+        world: Vec<Vec<f64>>,
+        kernel: Kernel,
+        growth_fn: impl Fn(f64) -> f64 + 'static,
+        dt: f64,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        let width = world.first().map_or(0, |row| row.len());
+        if width == 0 || world.iter().any(|row| row.len() != width) {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        let ca = Self {
+            world,
+            generation: 0,
+            kernel,
+            growth_fn: Box::new(growth_fn),
+            dt,
+            fft: None,
+        };
+
+        Ok(if ca.kernel.radius > DIRECT_CONVOLUTION_MAX_RADIUS {
+            ca.with_convolution(Convolution::Fft)
+        } else {
+            ca
+        })
+    }
+
+    pub fn with_convolution(mut self, convolution: Convolution) -> Self {
+        self.fft = match convolution {
+            Convolution::Direct => None,
+            Convolution::Fft => Some(FftConvolution::new(
+                &self.kernel,
+                self.world.len(),
+                self.world[0].len(),
+            )),
+        };
+        self
+    }
+
+    pub fn convolution(&self) -> Convolution {
+        match self.fft {
+            Some(_) => Convolution::Fft,
+            None => Convolution::Direct,
+        }
+    }
+
+    pub fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+
+    pub fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    // Total of all cell states
+    pub fn mass(&self) -> f64 {
+        self.world.iter().flatten().sum()
+    }
+
+    pub fn potential(&self) -> Vec<Vec<f64>> {
+        match &self.fft {
+            Some(fft) => fft.convolve(&self.world),
+            None => self.convolve_direct(),
+        }
+    }
+
+    fn convolve_direct(&self) -> Vec<Vec<f64>> {
+        let height = self.world.len() as isize;
+        let width = self.world[0].len() as isize;
+        let radius = self.kernel.radius as isize;
+
+        let taps: Vec<(isize, isize, f64)> = self
+            .kernel
+            .weights
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, &w)| w != 0.0)
+                    .map(move |(j, &w)| (i as isize - radius, j as isize - radius, w))
+            })
+            .collect();
+
+        (0..height)
+            .map(|i| {
+                (0..width)
+                    .map(|j| {
+                        taps.iter()
+                            .map(|&(dy, dx, w)| {
+                                let r = (i + dy).rem_euclid(height) as usize;
+                                let c = (j + dx).rem_euclid(width) as usize;
+                                w * self.world[r][c]
+                            })
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl CellularAutomaton for ContinuousCellularAutomaton {
+    type WorldType = Vec<Vec<f64>>;
+
+    fn step(&mut self) -> usize {
+        let potential = self.potential();
+
+        for (row, potential_row) in self.world.iter_mut().zip(potential) {
+            for (cell, u) in row.iter_mut().zip(potential_row) {
+                *cell = (*cell + self.dt * (self.growth_fn)(u)).clamp(0.0, 1.0);
+            }
+        }
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len(), self.world[0].len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// Single shell Lenia with the exponential kernel and Gaussian growth
+#[allow(non_snake_case)]
+pub fn LeniaCellularAutomaton(
+    world: Vec<Vec<f64>>,
+    radius: usize,
+    mu: f64,
+    sigma: f64,
+    dt: f64,
+) -> Result<ContinuousCellularAutomaton, CellularAutomatonWorldSizeError> {
+    let kernel = Kernel::lenia(radius, &[1.0], KernelCore::Exponential(4.0))
+        .map_err(|_| CellularAutomatonWorldSizeError)?;
+
+    ContinuousCellularAutomaton::new(world, kernel, gaussian_growth(mu, sigma), dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Orbium unicaudatus from Bert Chan's Lenia, for R = 13, T = 10,
+    // mu = 0.15 and sigma = 0.015, with states in percent
+    #[rustfmt::skip]
+    const ORBIUM: [[u8; 20]; 20] = [
+        [0, 0, 0, 0, 0, 0, 10, 14, 10, 0, 0, 3, 3, 0, 0, 30, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 8, 24, 30, 30, 18, 14, 15, 16, 15, 9, 20, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 15, 34, 44, 46, 38, 18, 14, 11, 13, 19, 18, 45, 0, 0, 0],
+        [0, 0, 0, 0, 6, 13, 39, 50, 50, 37, 6, 0, 0, 0, 2, 16, 68, 0, 0, 0],
+        [0, 0, 0, 11, 17, 17, 33, 40, 38, 28, 14, 0, 0, 0, 0, 0, 18, 42, 0, 0],
+        [0, 0, 9, 18, 13, 6, 8, 26, 32, 32, 27, 0, 0, 0, 0, 0, 0, 82, 0, 0],
+        [27, 0, 16, 12, 0, 0, 0, 25, 38, 44, 45, 34, 0, 0, 0, 0, 0, 22, 17, 0],
+        [0, 7, 20, 2, 0, 0, 0, 31, 48, 57, 60, 57, 0, 0, 0, 0, 0, 0, 49, 0],
+        [0, 59, 19, 0, 0, 0, 0, 20, 57, 69, 76, 76, 49, 0, 0, 0, 0, 0, 36, 0],
+        [0, 58, 19, 0, 0, 0, 0, 0, 67, 83, 90, 92, 87, 12, 0, 0, 0, 0, 22, 7],
+        [0, 0, 46, 0, 0, 0, 0, 0, 70, 93, 100, 100, 100, 61, 0, 0, 0, 0, 18, 11],
+        [0, 0, 82, 0, 0, 0, 0, 0, 47, 100, 100, 98, 100, 96, 27, 0, 0, 0, 19, 10],
+        [0, 0, 46, 0, 0, 0, 0, 0, 25, 100, 100, 84, 92, 97, 54, 14, 4, 10, 21, 5],
+        [0, 0, 0, 40, 0, 0, 0, 0, 9, 80, 100, 82, 80, 85, 63, 31, 18, 19, 20, 1],
+        [0, 0, 0, 36, 10, 0, 0, 0, 5, 54, 86, 79, 74, 72, 60, 39, 28, 24, 13, 0],
+        [0, 0, 0, 1, 30, 7, 0, 0, 8, 36, 64, 70, 64, 60, 51, 39, 29, 19, 4, 0],
+        [0, 0, 0, 0, 10, 24, 14, 10, 15, 29, 45, 53, 52, 46, 40, 31, 21, 8, 0, 0],
+        [0, 0, 0, 0, 0, 8, 21, 21, 22, 29, 36, 39, 37, 33, 26, 18, 9, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 3, 13, 19, 22, 24, 24, 23, 18, 13, 5, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 2, 6, 8, 9, 7, 5, 1, 0, 0, 0, 0, 0],
+    ];
+
+    fn orbium_world(size: usize, offset: usize) -> Vec<Vec<f64>> {
+        let mut world = vec![vec![0.0; size]; size];
+        for (i, row) in ORBIUM.iter().enumerate() {
+            for (j, &v) in row.iter().enumerate() {
+                world[i + offset][j + offset] = v as f64 / 100.0;
+            }
+        }
+        world
+    }
+
+    fn centroid(world: &[Vec<f64>]) -> (f64, f64) {
+        let mass: f64 = world.iter().flatten().sum();
+        let (mut y, mut x) = (0.0, 0.0);
+        for (i, row) in world.iter().enumerate() {
+            for (j, &v) in row.iter().enumerate() {
+                y += i as f64 * v;
+                x += j as f64 * v;
+            }
+        }
+        (y / mass, x / mass)
+    }
+
+    #[test]
+    fn test_kernel_normalised() {
+        let kernel =
+            Kernel::lenia(13, &[1.0], KernelCore::Exponential(4.0)).expect("Construction failed");
+        let total: f64 = kernel.weights().iter().flatten().sum();
+
+        assert_eq!(kernel.radius(), 13);
+        assert!((total - 1.0).abs() < 1e-9);
+        // The centre of a ring kernel is empty
+        assert_eq!(kernel.weights()[13][13], 0.0);
+    }
+
+    #[test]
+    fn test_kernel_shells() {
+        let kernel = Kernel::lenia(10, &[1.0, 0.5], KernelCore::Step).expect("Construction failed");
+        let w = kernel.weights();
+
+        // Each shell spans 5 cells, with its step active on the middle half
+        assert!(w[10][12] > 0.0);
+        assert!(w[10][17] > 0.0);
+        assert!((w[10][12] - 2.0 * w[10][17]).abs() < 1e-12);
+        assert_eq!(w[10][15], 0.0);
+    }
+
+    #[test]
+    fn test_invalid_kernel() {
+        assert_eq!(Kernel::new(vec![vec![1.0; 2]; 2]), Err(InvalidKernelError));
+        assert_eq!(Kernel::new(vec![vec![0.0; 3]; 3]), Err(InvalidKernelError));
+        assert_eq!(
+            Kernel::lenia(0, &[1.0], KernelCore::Step),
+            Err(InvalidKernelError)
+        );
+    }
+
+    #[test]
+    fn test_growth_functions() {
+        let gaussian = gaussian_growth(0.15, 0.015);
+        let polynomial = polynomial_growth(0.15, 0.015);
+        let step = step_growth(0.15, 0.015);
+
+        assert!((gaussian(0.15) - 1.0).abs() < 1e-12);
+        assert!((gaussian(0.0) + 1.0).abs() < 1e-12);
+        assert!((polynomial(0.15) - 1.0).abs() < 1e-12);
+        assert_eq!(polynomial(0.3), -1.0);
+        assert_eq!(step(0.16), 1.0);
+        assert_eq!(step(0.2), -1.0);
+    }
+
+    #[test]
+    fn test_fft_matches_direct() {
+        let world: Vec<Vec<f64>> = (0..24)
+            .map(|i| {
+                (0..20)
+                    .map(|j| ((i * 7 + j * 13) % 11) as f64 / 10.0)
+                    .collect()
+            })
+            .collect();
+        let kernel =
+            Kernel::lenia(6, &[0.5, 1.0], KernelCore::Polynomial(4)).expect("Construction failed");
+
+        let fft = ContinuousCellularAutomaton::new(world.clone(), kernel.clone(), |u| u, 0.1)
+            .expect("Construction failed");
+        let direct = ContinuousCellularAutomaton::new(world, kernel, |u| u, 0.1)
+            .expect("Construction failed")
+            .with_convolution(Convolution::Direct);
+
+        assert_eq!(fft.convolution(), Convolution::Fft);
+        for (a, b) in fft
+            .potential()
+            .iter()
+            .flatten()
+            .zip(direct.potential().iter().flatten())
+        {
+            assert!((a - b).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_small_kernel_wraps() {
+        let kernel = Kernel::new(vec![
+            vec![0.0, 1.0, 0.0],
+            vec![0.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0],
+        ])
+        .expect("Construction failed");
+        let mut world = vec![vec![0.0; 4]; 4];
+        world[3][1] = 1.0;
+
+        let ca = ContinuousCellularAutomaton::new(world, kernel, |u| u, 1.0)
+            .expect("Construction failed");
+
+        // Each cell sees the one above it, so row 0 sees row 3
+        assert_eq!(ca.convolution(), Convolution::Direct);
+        assert_eq!(ca.potential()[0][1], 1.0);
+        assert_eq!(ca.potential()[3][1], 0.0);
+    }
+
+    #[test]
+    fn test_orbium_glides() {
+        let mut ca = LeniaCellularAutomaton(orbium_world(96, 38), 13, 0.15, 0.015, 0.1)
+            .expect("Construction failed");
+
+        let mass = ca.mass();
+        let (y0, x0) = centroid(&ca.world());
+        for _ in 0..30 {
+            ca.step();
+        }
+        let (y1, x1) = centroid(&ca.world());
+
+        // The creature neither dies nor spreads, and has moved away
+        assert!((ca.mass() - mass).abs() / mass < 0.1);
+        assert!(((y1 - y0).powi(2) + (x1 - x0).powi(2)).sqrt() > 5.0);
+    }
+}
@@ -3,8 +3,8 @@ use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
 
 use crate::dim2::{CellularAutomaton2d, Neighbors2d};
 
-fn conway_neighbors(
-    world: &Vec<Vec<bool>>,
+pub(crate) fn conway_neighbors(
+    world: &[Vec<bool>],
     i: usize,
     j: usize,
     wrapping: bool,
@@ -14,12 +14,12 @@ fn conway_neighbors(
 
     let mut neighbors = [[false; 3]; 3];
 
-    for x in 0..3 {
-        for y in 0..3 {
+    for (x, row) in neighbors.iter_mut().enumerate() {
+        for (y, cell) in row.iter_mut().enumerate() {
             let n_i = i as isize + x as isize - 1;
             let n_j = j as isize + y as isize - 1;
 
-            neighbors[x][y] = if wrapping {
+            *cell = if wrapping {
                 world[((n_i + height) % height) as usize][((n_j + width) % width) as usize]
             } else if n_i >= 0 && n_i < height && n_j >= 0 && n_j < width {
                 world[n_i as usize][n_j as usize]
@@ -32,7 +32,7 @@ fn conway_neighbors(
     Neighbors2d::Neighborhood(neighbors)
 }
 
-fn conway_evolve(neighbors: [[bool; 3]; 3]) -> bool {
+pub(crate) fn conway_evolve(neighbors: [[bool; 3]; 3]) -> bool {
     let live: u8 = neighbors
         .iter()
         .flat_map(|row| row.iter())
@@ -41,7 +41,7 @@ fn conway_evolve(neighbors: [[bool; 3]; 3]) -> bool {
         .filter(|(_, &cell)| cell)
         .count() as u8;
 
-    (neighbors[1][1] && (live == 2 || live == 3)) || (!neighbors[1][1] && live == 3)
+    live == 3 || (neighbors[1][1] && live == 2)
 }
 
 #[allow(non_snake_case)]
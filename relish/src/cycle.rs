@@ -0,0 +1,271 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::automaton::CellularAutomaton;
+
+// Generations `start - transient` to `start - 1` are never seen again, and
+// from generation `start` on the world repeats every `period` generations.
+// Generations are counted by `age()`, so an automaton that has already been
+// stepped reports a `start` past its transient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub transient: usize,
+    pub period: usize,
+    pub start: usize,
+}
+
+// Steps `ca` until a world repeats, remembering every world seen. Exact, but
+// memory grows with the length of the run. Gives up after `max_steps` steps,
+// which must cover the transient plus one period. `ca` is left one period
+// into the cycle.
+pub fn find_cycle<C>(ca: &mut C, max_steps: usize) -> Option<Cycle>
+where
+    C: CellularAutomaton,
+    C::WorldType: Hash + Eq,
+{
+    let first = ca.age();
+    let mut seen = HashMap::new();
+    seen.insert(ca.world(), first);
+
+    for _ in 0..max_steps {
+        ca.step();
+        let age = ca.age();
+        let world = ca.world();
+
+        if let Some(&start) = seen.get(&world) {
+            return Some(Cycle {
+                transient: start - first,
+                period: age - start,
+                start,
+            });
+        }
+        seen.insert(world, age);
+    }
+
+    None
+}
+
+fn world_hash<W: Hash>(world: &W) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    world.hash(&mut hasher);
+    hasher.finish()
+}
+
+// As `find_cycle`, but remembering only a 64-bit hash of each world, so
+// memory is 8 bytes per generation whatever the world size. Two different
+// worlds with the same hash would be reported as a cycle; at 64 bits that
+// is vanishingly rare for runs of any practical length.
+pub fn find_cycle_hashed<C>(ca: &mut C, max_steps: usize) -> Option<Cycle>
+where
+    C: CellularAutomaton,
+    C::WorldType: Hash,
+{
+    let first = ca.age();
+    let mut seen = HashMap::new();
+    seen.insert(world_hash(&ca.world()), first);
+
+    for _ in 0..max_steps {
+        ca.step();
+        let age = ca.age();
+
+        if let Some(start) = seen.insert(world_hash(&ca.world()), age) {
+            return Some(Cycle {
+                transient: start - first,
+                period: age - start,
+                start,
+            });
+        }
+    }
+
+    None
+}
+
+// Brent's algorithm: finds the cycle while holding at most two automata and
+// one saved world, however long the run. The transient is found by running
+// the automaton again from the start, so `make` must build the same
+// automaton every time it is called. The first pass takes up to
+// `max_steps` steps, which may need to be up to about twice the transient
+// plus period to find the cycle.
+pub fn find_cycle_brent<C, F>(mut make: F, max_steps: usize) -> Option<Cycle>
+where
+    C: CellularAutomaton,
+    C::WorldType: PartialEq,
+    F: FnMut() -> C,
+{
+    if max_steps == 0 {
+        return None;
+    }
+
+    // Find the period: the hare runs ahead while the tortoise jumps to it
+    // at every power of two
+    let mut hare = make();
+    let first = hare.age();
+    let mut tortoise = hare.world();
+    let mut power = 1;
+    let mut period = 1;
+    let mut steps = 1;
+
+    hare.step();
+    while hare.world() != tortoise {
+        if steps == max_steps {
+            return None;
+        }
+        if power == period {
+            tortoise = hare.world();
+            power *= 2;
+            period = 0;
+        }
+        hare.step();
+        period += 1;
+        steps += 1;
+    }
+
+    // Find the transient: with the hare one period ahead, they meet at the
+    // first generation of the cycle
+    let mut tortoise = make();
+    let mut hare = make();
+    for _ in 0..period {
+        hare.step();
+    }
+
+    let mut transient = 0;
+    while tortoise.world() != hare.world() {
+        tortoise.step();
+        hare.step();
+        transient += 1;
+    }
+
+    Some(Cycle {
+        transient,
+        period,
+        start: first + transient,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conway::ConwayCellularAutomaton;
+    use crate::elementary::ElementaryCellularAutomaton;
+
+    fn world_with(height: usize, width: usize, cells: &[(usize, usize)]) -> Vec<Vec<bool>> {
+        let mut world = vec![vec![false; width]; height];
+        for &(i, j) in cells {
+            world[i][j] = true;
+        }
+        world
+    }
+
+    #[test]
+    fn test_blinker() {
+        let world = world_with(5, 5, &[(2, 1), (2, 2), (2, 3)]);
+        let expected = Cycle {
+            transient: 0,
+            period: 2,
+            start: 0,
+        };
+
+        let mut ca = ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        assert_eq!(find_cycle(&mut ca, 10), Some(expected));
+        assert_eq!(ca.age(), 2);
+
+        let mut ca = ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        assert_eq!(find_cycle_hashed(&mut ca, 10), Some(expected));
+
+        let make = || ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        assert_eq!(find_cycle_brent(make, 10), Some(expected));
+    }
+
+    #[test]
+    fn test_wrapping_spaceship() {
+        // The lightweight spaceship from the Conway tests crosses a 10 x 10
+        // torus and returns after 20 generations
+        let world = world_with(
+            10,
+            10,
+            &[
+                (2, 3),
+                (2, 6),
+                (3, 2),
+                (4, 2),
+                (4, 6),
+                (5, 2),
+                (5, 3),
+                (5, 4),
+                (5, 5),
+            ],
+        );
+
+        let mut ca = ConwayCellularAutomaton(world.clone(), true).expect("Construction failed");
+        let cycle = find_cycle(&mut ca, 100).expect("No cycle found");
+        assert_eq!((cycle.transient, cycle.period), (0, 20));
+
+        let make = || ConwayCellularAutomaton(world.clone(), true).expect("Construction failed");
+        assert_eq!(find_cycle_brent(make, 100), Some(cycle));
+    }
+
+    #[test]
+    fn test_transient() {
+        // An L tromino becomes a block after one generation
+        let world = world_with(6, 6, &[(2, 2), (2, 3), (3, 2)]);
+        let expected = Cycle {
+            transient: 1,
+            period: 1,
+            start: 1,
+        };
+
+        let mut ca = ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        assert_eq!(find_cycle(&mut ca, 10), Some(expected));
+
+        let make = || ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        assert_eq!(find_cycle_brent(make, 10), Some(expected));
+
+        // Starting later counts generations from the automaton's age
+        let mut ca = ConwayCellularAutomaton(world, false).expect("Construction failed");
+        for _ in 0..3 {
+            ca.step();
+        }
+        assert_eq!(
+            find_cycle_hashed(&mut ca, 10),
+            Some(Cycle {
+                transient: 0,
+                period: 1,
+                start: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_modes_agree() {
+        for (rule, seed) in [(30u8, 0x5a3u64), (45, 0x1c7), (90, 0x801), (110, 0x2f1)] {
+            let world: Vec<bool> = (0..14).map(|k| (seed >> k) & 1 == 1).collect();
+
+            let mut ca =
+                ElementaryCellularAutomaton(world.clone(), rule).expect("Construction failed");
+            let exact = find_cycle(&mut ca, 1 << 14).expect("No cycle found");
+
+            let mut ca =
+                ElementaryCellularAutomaton(world.clone(), rule).expect("Construction failed");
+            assert_eq!(find_cycle_hashed(&mut ca, 1 << 14), Some(exact));
+
+            let make =
+                || ElementaryCellularAutomaton(world.clone(), rule).expect("Construction failed");
+            assert_eq!(find_cycle_brent(make, 1 << 15), Some(exact));
+        }
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let world = world_with(5, 5, &[(2, 1), (2, 2), (2, 3)]);
+
+        let mut ca = ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        assert_eq!(find_cycle(&mut ca, 1), None);
+        assert_eq!(ca.age(), 1);
+
+        let make = || ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        assert_eq!(find_cycle_brent(make, 0), None);
+        assert_eq!(find_cycle_brent(make, 1), None);
+    }
+}
@@ -0,0 +1,316 @@
+use rand::{Rng, SeedableRng};
+
+use crate::automaton::{CellularAutomatonWorldSizeError, NeighborhoodShape};
+
+use crate::dim2::{CellularAutomaton2d, Neighbors2d};
+use crate::stochastic::StochasticRng;
+
+// Gathers the SIZE x SIZE square around a cell. Without wrapping, cells beyond
+// the border copy the centre cell, which never counts towards a transition.
+pub(crate) fn range_neighbors<const SIZE: usize>(
+    world: &[Vec<u8>],
+    i: usize,
+    j: usize,
+    wrapping: bool,
+) -> Neighbors2d<u8, SIZE, SIZE> {
+    let height = world.len() as isize;
+    let width = world[0].len() as isize;
+    let range = (SIZE / 2) as isize;
+
+    let mut neighbors = [[world[i][j]; SIZE]; SIZE];
+
+    for (x, row) in neighbors.iter_mut().enumerate() {
+        for (y, cell) in row.iter_mut().enumerate() {
+            let n_i = i as isize + x as isize - range;
+            let n_j = j as isize + y as isize - range;
+
+            if wrapping {
+                *cell = world[n_i.rem_euclid(height) as usize][n_j.rem_euclid(width) as usize];
+            } else if n_i >= 0 && n_i < height && n_j >= 0 && n_j < width {
+                *cell = world[n_i as usize][n_j as usize];
+            }
+        }
+    }
+
+    Neighbors2d::Neighborhood(neighbors)
+}
+
+fn count_in_shape<const SIZE: usize>(
+    neighbors: &[[u8; SIZE]; SIZE],
+    shape: NeighborhoodShape,
+    state: u8,
+) -> usize {
+    let range = SIZE / 2;
+
+    neighbors
+        .iter()
+        .enumerate()
+        .flat_map(|(x, row)| row.iter().enumerate().map(move |(y, &cell)| (x, y, cell)))
+        .filter(|&(x, y, cell)| {
+            (x, y) != (range, range) && cell == state && shape.contains(x, y, range)
+        })
+        .count()
+}
+
+fn valid_world(world: &[Vec<u8>], states: u8) -> bool {
+    let width = world.first().map_or(0, |row| row.len());
+
+    width > 0
+        && world
+            .iter()
+            .all(|row| row.len() == width && row.iter().all(|&cell| cell < states))
+}
+
+// Griffeath's cyclic automaton: a cell of colour `c` advances to `c + 1`
+// (mod `states`) once at least `threshold` neighbours already show that
+// colour. The range is `SIZE / 2`, so e.g. `SIZE = 3` is range 1.
+#[allow(non_snake_case)]
+pub fn CyclicCellularAutomaton<const SIZE: usize>(
+    world: Vec<Vec<u8>>,
+    states: u8,
+    threshold: usize,
+    shape: NeighborhoodShape,
+    wrapping: bool,
+) -> Result<CellularAutomaton2d<u8, SIZE, SIZE>, CellularAutomatonWorldSizeError> {
+    if SIZE % 2 != 1 || states < 2 || threshold == 0 || !valid_world(&world, states) {
+        return Err(CellularAutomatonWorldSizeError);
+    }
+
+    CellularAutomaton2d::<u8, SIZE, SIZE>::new(
+        world,
+        move |neighbors: [[u8; SIZE]; SIZE]| {
+            let cell = neighbors[SIZE / 2][SIZE / 2];
+            let next = (cell + 1) % states;
+
+            if count_in_shape(&neighbors, shape, next) >= threshold {
+                next
+            } else {
+                cell
+            }
+        },
+        move |world, i, j| range_neighbors(world, i, j, wrapping),
+    )
+}
+
+// Greenberg–Hastings excitable medium: 0 is resting, 1 excited and
+// `2..states` refractory. A resting cell is excited by at least `threshold`
+// excited neighbours, every other cell moves on to the next state and back
+// to rest.
+#[allow(non_snake_case)]
+pub fn GreenbergHastingsCellularAutomaton<const SIZE: usize>(
+    world: Vec<Vec<u8>>,
+    states: u8,
+    threshold: usize,
+    shape: NeighborhoodShape,
+    wrapping: bool,
+) -> Result<CellularAutomaton2d<u8, SIZE, SIZE>, CellularAutomatonWorldSizeError> {
+    if SIZE % 2 != 1 || states < 3 || threshold == 0 || !valid_world(&world, states) {
+        return Err(CellularAutomatonWorldSizeError);
+    }
+
+    CellularAutomaton2d::<u8, SIZE, SIZE>::new(
+        world,
+        move |neighbors: [[u8; SIZE]; SIZE]| match neighbors[SIZE / 2][SIZE / 2] {
+            0 if count_in_shape(&neighbors, shape, 1) >= threshold => 1,
+            0 => 0,
+            cell => (cell + 1) % states,
+        },
+        move |world, i, j| range_neighbors(world, i, j, wrapping),
+    )
+}
+
+// A world of uniformly random states, reproducible from the seed
+pub fn random_soup(height: usize, width: usize, states: u8, seed: u64) -> Vec<Vec<u8>> {
+    let mut rng = StochasticRng::seed_from_u64(seed);
+
+    (0..height)
+        .map(|_| (0..width).map(|_| rng.gen_range(0..states)).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::CellularAutomaton;
+
+    // Fraction of cells that changed state in one step
+    fn activity<C: CellularAutomaton<WorldType = Vec<Vec<u8>>>>(ca: &mut C) -> f64 {
+        let before = ca.world();
+        ca.step();
+        let after = ca.world();
+
+        let changed = before
+            .iter()
+            .flatten()
+            .zip(after.iter().flatten())
+            .filter(|(b, a)| b != a)
+            .count();
+        changed as f64 / (after.len() * after[0].len()) as f64
+    }
+
+    #[test]
+    fn test_cyclic_advance() {
+        let world = vec![vec![0, 1, 0], vec![0, 0, 0], vec![2, 0, 3]];
+        let mut ca =
+            CyclicCellularAutomaton::<3>(world, 4, 1, NeighborhoodShape::VonNeumann, false)
+                .expect("Construction failed");
+        ca.step();
+
+        // The orthogonal neighbours of the 1 advance, and so does the 3, which
+        // wraps round to 0 next to the 0 above it
+        assert_eq!(
+            ca.world(),
+            vec![vec![1, 1, 1], vec![0, 1, 0], vec![2, 0, 0]]
+        );
+    }
+
+    #[test]
+    fn test_cyclic_threshold() {
+        let world = vec![vec![1, 1, 0], vec![0, 0, 0], vec![0, 0, 0]];
+        let mut ca = CyclicCellularAutomaton::<3>(world, 3, 2, NeighborhoodShape::Moore, false)
+            .expect("Construction failed");
+        ca.step();
+
+        assert_eq!(
+            ca.world(),
+            vec![vec![1, 1, 0], vec![1, 1, 0], vec![0, 0, 0]]
+        );
+    }
+
+    #[test]
+    fn test_greenberg_hastings_ring_dies_out() {
+        let mut world = vec![vec![0; 15]; 15];
+        world[7][7] = 1;
+
+        let mut ca = GreenbergHastingsCellularAutomaton::<3>(
+            world,
+            3,
+            1,
+            NeighborhoodShape::VonNeumann,
+            false,
+        )
+        .expect("Construction failed");
+
+        ca.step();
+        assert_eq!(ca.world()[7][7], 2);
+        assert_eq!(ca.world()[6][7], 1);
+        assert_eq!(ca.world()[6][6], 0);
+
+        // A wave from a single seed leaves the bounded world and never returns
+        for _ in 0..30 {
+            ca.step();
+        }
+        assert_eq!(ca.world(), vec![vec![0; 15]; 15]);
+    }
+
+    #[test]
+    fn test_random_soup() {
+        let soup = random_soup(20, 30, 5, 9);
+
+        assert_eq!(soup, random_soup(20, 30, 5, 9));
+        assert_ne!(soup, random_soup(20, 30, 5, 10));
+        assert_eq!((soup.len(), soup[0].len()), (20, 30));
+        assert!(soup.iter().flatten().all(|&cell| cell < 5));
+        for state in 0..5 {
+            assert!(soup.iter().flatten().any(|&cell| cell == state));
+        }
+    }
+
+    #[test]
+    fn test_cyclic_spirals_take_over() {
+        // With 8 colours and range 1 von Neumann neighbours, spiral demons
+        // grow from the soup until every cell cycles through all colours
+        let mut ca = CyclicCellularAutomaton::<3>(
+            random_soup(48, 48, 8, 1),
+            8,
+            1,
+            NeighborhoodShape::VonNeumann,
+            true,
+        )
+        .expect("Construction failed");
+
+        let early = activity(&mut ca);
+        for _ in 0..600 {
+            ca.step();
+        }
+        let late = activity(&mut ca);
+
+        let settled = ca.world();
+        for _ in 0..8 {
+            ca.step();
+        }
+
+        assert!(early < 0.5);
+        assert_eq!(late, 1.0);
+        assert_eq!(ca.world(), settled);
+    }
+
+    #[test]
+    fn test_greenberg_hastings_soup_sustains_spirals() {
+        let mut ca = GreenbergHastingsCellularAutomaton::<3>(
+            random_soup(40, 40, 4, 3),
+            4,
+            1,
+            NeighborhoodShape::Moore,
+            true,
+        )
+        .expect("Construction failed");
+
+        for _ in 0..300 {
+            ca.step();
+        }
+        let settled = ca.world();
+        let late = activity(&mut ca);
+        for _ in 1..4 {
+            ca.step();
+        }
+
+        assert!(late > 0.3);
+        assert_eq!(ca.world(), settled);
+    }
+
+    #[test]
+    fn test_cyclic_high_threshold_fixates() {
+        // Too few colours for the threshold: the soup freezes into debris
+        let mut ca = CyclicCellularAutomaton::<3>(
+            random_soup(48, 48, 4, 0),
+            4,
+            2,
+            NeighborhoodShape::VonNeumann,
+            true,
+        )
+        .expect("Construction failed");
+
+        for _ in 0..100 {
+            ca.step();
+        }
+        assert_eq!(activity(&mut ca), 0.0);
+    }
+
+    #[test]
+    fn test_invalid_cyclic_parameters() {
+        let soup = random_soup(4, 4, 3, 0);
+
+        assert!(
+            CyclicCellularAutomaton::<3>(soup.clone(), 2, 1, NeighborhoodShape::Moore, true)
+                .is_err()
+        );
+        assert!(
+            CyclicCellularAutomaton::<4>(soup.clone(), 3, 1, NeighborhoodShape::Moore, true)
+                .is_err()
+        );
+        assert!(
+            CyclicCellularAutomaton::<3>(soup.clone(), 3, 0, NeighborhoodShape::Moore, true)
+                .is_err()
+        );
+        assert!(GreenbergHastingsCellularAutomaton::<3>(
+            soup,
+            2,
+            1,
+            NeighborhoodShape::Moore,
+            true
+        )
+        .is_err());
+    }
+}
@@ -1,15 +1,21 @@
 use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+use crate::update::{UpdatePlan, UpdateSchedule, UpdateScheme, UpdateSchemeError};
 
 pub enum Neighbors1d<CellType, const WIDTH: usize> {
     Neighborhood([CellType; WIDTH]),
     Edge,
 }
 
+// The boxed neighbourhood function, for automata that wrap their own
+pub(crate) type NeighborhoodFn1d<CellType, const WIDTH: usize> =
+    Box<dyn Fn(&[CellType], usize) -> Neighbors1d<CellType, WIDTH>>;
+
 pub struct CellularAutomaton1d<CellType: Clone, const WIDTH: usize> {
     world: Vec<CellType>,
     generation: usize,
     evolvution_fn: Box<dyn Fn([CellType; WIDTH]) -> CellType>,
-    neighborhood_fn: Box<dyn Fn(&[CellType], usize) -> Neighbors1d<CellType, WIDTH>>,
+    neighborhood_fn: NeighborhoodFn1d<CellType, WIDTH>,
+    schedule: Option<UpdateSchedule>,
 }
 
 impl<CellType: Clone, const WIDTH: usize> CellularAutomaton1d<CellType, WIDTH> {
@@ -27,8 +33,35 @@ impl<CellType: Clone, const WIDTH: usize> CellularAutomaton1d<CellType, WIDTH> {
             generation: 0,
             evolvution_fn: Box::new(evolvution_fn),
             neighborhood_fn: Box::new(neighborhood_fn),
+            schedule: None,
         })
     }
+
+    // Switches to another update scheme; `seed` drives any randomness in it
+    pub fn with_update_scheme(
+        mut self,
+        scheme: UpdateScheme,
+        seed: u64,
+    ) -> Result<Self, UpdateSchemeError> {
+        self.schedule = match scheme {
+            UpdateScheme::Synchronous => None,
+            _ => Some(UpdateSchedule::new(scheme, seed, self.world.len())?),
+        };
+        Ok(self)
+    }
+
+    pub fn update_scheme(&self) -> UpdateScheme {
+        self.schedule
+            .as_ref()
+            .map_or(UpdateScheme::Synchronous, |s| s.scheme())
+    }
+
+    fn evolve_cell(&self, world: &[CellType], i: usize) -> CellType {
+        match (self.neighborhood_fn)(world, i) {
+            Neighbors1d::Neighborhood(neighbors) => (self.evolvution_fn)(neighbors),
+            Neighbors1d::Edge => world[i].clone(),
+        }
+    }
 }
 
 impl<CellType: Clone, const WIDTH: usize> CellularAutomaton
@@ -37,14 +70,29 @@ impl<CellType: Clone, const WIDTH: usize> CellularAutomaton
     type WorldType = Vec<CellType>;
 
     fn step(&mut self) -> usize {
-        let prev_world = &self.world.clone()[..];
-
-        self.world = (0..self.size()[0])
-            .map(|i| match (self.neighborhood_fn)(prev_world, i) {
-                Neighbors1d::Neighborhood(neighbors) => (self.evolvution_fn)(neighbors),
-                Neighbors1d::Edge => prev_world[i].clone(),
-            })
-            .collect();
+        let cells = self.size()[0];
+        let plan = match self.schedule.as_mut() {
+            Some(schedule) => schedule.plan(self.generation, cells),
+            None => UpdatePlan::Synchronous(None),
+        };
+
+        match plan {
+            UpdatePlan::Synchronous(mask) => {
+                let prev_world = &self.world.clone()[..];
+
+                self.world = (0..cells)
+                    .map(|i| match &mask {
+                        Some(mask) if !mask[i] => prev_world[i].clone(),
+                        _ => self.evolve_cell(prev_world, i),
+                    })
+                    .collect();
+            }
+            UpdatePlan::Sequential(order) => {
+                for i in order {
+                    self.world[i] = self.evolve_cell(&self.world, i);
+                }
+            }
+        }
         self.generation += 1;
         self.generation
     }
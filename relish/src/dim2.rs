@@ -1,16 +1,21 @@
 use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+use crate::update::{UpdatePlan, UpdateSchedule, UpdateScheme, UpdateSchemeError};
 
 pub enum Neighbors2d<CellType, const HEIGHT: usize, const WIDTH: usize> {
     Neighborhood([[CellType; WIDTH]; HEIGHT]),
     Edge,
 }
 
+// The boxed neighbourhood function, for automata that wrap their own
+pub(crate) type NeighborhoodFn2d<CellType, const HEIGHT: usize, const WIDTH: usize> =
+    Box<dyn Fn(&Vec<Vec<CellType>>, usize, usize) -> Neighbors2d<CellType, HEIGHT, WIDTH>>;
+
 pub struct CellularAutomaton2d<CellType: Clone, const HEIGHT: usize, const WIDTH: usize> {
     world: Vec<Vec<CellType>>,
     generation: usize,
     evolvution_fn: Box<dyn Fn([[CellType; WIDTH]; HEIGHT]) -> CellType>,
-    neighborhood_fn:
-        Box<dyn Fn(&Vec<Vec<CellType>>, usize, usize) -> Neighbors2d<CellType, HEIGHT, WIDTH>>,
+    neighborhood_fn: NeighborhoodFn2d<CellType, HEIGHT, WIDTH>,
+    schedule: Option<UpdateSchedule>,
 }
 
 impl<CellType: Clone, const HEIGHT: usize, const WIDTH: usize>
@@ -29,8 +34,37 @@ impl<CellType: Clone, const HEIGHT: usize, const WIDTH: usize>
             generation: 0,
             evolvution_fn: Box::new(evolvution_fn),
             neighborhood_fn: Box::new(neighborhood_fn),
+            schedule: None,
         })
     }
+
+    // Switches to another update scheme; `seed` drives any randomness in it.
+    // Cells are numbered in row-major order.
+    pub fn with_update_scheme(
+        mut self,
+        scheme: UpdateScheme,
+        seed: u64,
+    ) -> Result<Self, UpdateSchemeError> {
+        let cells = self.world.iter().map(|row| row.len()).sum();
+        self.schedule = match scheme {
+            UpdateScheme::Synchronous => None,
+            _ => Some(UpdateSchedule::new(scheme, seed, cells)?),
+        };
+        Ok(self)
+    }
+
+    pub fn update_scheme(&self) -> UpdateScheme {
+        self.schedule
+            .as_ref()
+            .map_or(UpdateScheme::Synchronous, |s| s.scheme())
+    }
+
+    fn evolve_cell(&self, world: &Vec<Vec<CellType>>, i: usize, j: usize) -> CellType {
+        match (self.neighborhood_fn)(world, i, j) {
+            Neighbors2d::Neighborhood(neighbors) => (self.evolvution_fn)(neighbors),
+            Neighbors2d::Edge => world[i][j].clone(),
+        }
+    }
 }
 
 impl<CellType: Clone, const S: usize, const T: usize> CellularAutomaton
@@ -38,16 +72,32 @@ impl<CellType: Clone, const S: usize, const T: usize> CellularAutomaton
 {
     type WorldType = Vec<Vec<CellType>>;
     fn step(&mut self) -> usize {
-        let prev_world = &self.world.clone();
-
         let world_size = self.size();
-
-        for i in 0..world_size[0] {
-            for j in 0..world_size[1] {
-                self.world[i][j] = match (self.neighborhood_fn)(prev_world, i, j) {
-                    Neighbors2d::Neighborhood(neighbors) => (self.evolvution_fn)(neighbors),
-                    Neighbors2d::Edge => prev_world[i][j].clone(),
-                };
+        let plan = match self.schedule.as_mut() {
+            Some(schedule) => schedule.plan(self.generation, world_size[0] * world_size[1]),
+            None => UpdatePlan::Synchronous(None),
+        };
+
+        match plan {
+            UpdatePlan::Synchronous(mask) => {
+                let prev_world = &self.world.clone();
+
+                for i in 0..world_size[0] {
+                    for j in 0..world_size[1] {
+                        if let Some(mask) = &mask {
+                            if !mask[i * world_size[1] + j] {
+                                continue;
+                            }
+                        }
+                        self.world[i][j] = self.evolve_cell(prev_world, i, j);
+                    }
+                }
+            }
+            UpdatePlan::Sequential(order) => {
+                for cell in order {
+                    let (i, j) = (cell / world_size[1], cell % world_size[1]);
+                    self.world[i][j] = self.evolve_cell(&self.world, i, j);
+                }
             }
         }
         self.generation += 1;
@@ -5,7 +5,7 @@ use crate::automaton::CellularAutomatonWorldSizeError;
 
 use crate::dim1::{CellularAutomaton1d, Neighbors1d};
 
-fn elementary_evolve_builder(pattern: u8) -> impl Fn([bool; 3]) -> bool {
+pub(crate) fn elementary_evolve_builder(pattern: u8) -> impl Fn([bool; 3]) -> bool {
     move |values: [bool; 3]| {
         let mut val = (values[0] as u8) << 2;
         val |= (values[1] as u8) << 1;
@@ -16,7 +16,7 @@ fn elementary_evolve_builder(pattern: u8) -> impl Fn([bool; 3]) -> bool {
     }
 }
 
-fn elementary_neighbor_fn(world: &[bool], i: usize) -> Neighbors1d<bool, 3> {
+pub(crate) fn elementary_neighbor_fn(world: &[bool], i: usize) -> Neighbors1d<bool, 3> {
     if i < 1 || i > (world.len() - 2) {
         return Neighbors1d::Edge;
     }
@@ -0,0 +1,1057 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::automaton::CellularAutomatonWorldSizeError;
+
+use crate::dim1::{CellularAutomaton1d, Neighbors1d};
+use crate::dim2::{CellularAutomaton2d, Neighbors2d};
+
+// Neighbours are listed in Golly's order: clockwise from north for the 2D
+// neighbourhoods (N, NE, E, SE, S, SW, W, NW for Moore, N, E, S, W for von
+// Neumann and N, E, SE, S, W, NW for hexagonal, which leaves out NE and SW of
+// the square grid) and W, E for one-dimensional rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GollyNeighborhood {
+    Moore,
+    VonNeumann,
+    Hexagonal,
+    OneDimensional,
+}
+
+impl GollyNeighborhood {
+    pub fn neighbors(&self) -> usize {
+        match self {
+            GollyNeighborhood::Moore => 8,
+            GollyNeighborhood::VonNeumann => 4,
+            GollyNeighborhood::Hexagonal => 6,
+            GollyNeighborhood::OneDimensional => 2,
+        }
+    }
+
+    // Positions in a 3x3 block, in the order above
+    fn offsets(&self) -> &'static [(usize, usize)] {
+        match self {
+            GollyNeighborhood::Moore => &[
+                (0, 1),
+                (0, 2),
+                (1, 2),
+                (2, 2),
+                (2, 1),
+                (2, 0),
+                (1, 0),
+                (0, 0),
+            ],
+            GollyNeighborhood::VonNeumann => &[(0, 1), (1, 2), (2, 1), (1, 0)],
+            GollyNeighborhood::Hexagonal => &[(0, 1), (1, 2), (2, 2), (2, 1), (1, 0), (0, 0)],
+            GollyNeighborhood::OneDimensional => &[(1, 0), (1, 2)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GollyRuleErrorKind {
+    MissingRule,
+    Syntax,
+    UnknownVariable,
+    UnsupportedNeighborhood,
+    UnsupportedSymmetry,
+    InvalidState,
+    InvalidTree,
+}
+
+// `line` is 1-based within the whole file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GollyRuleError {
+    pub line: usize,
+    pub kind: GollyRuleErrorKind,
+}
+
+fn rule_error(line: usize, kind: GollyRuleErrorKind) -> GollyRuleError {
+    GollyRuleError { line, kind }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Term {
+    State(u8),
+    Variable(usize),
+}
+
+#[derive(Debug, Clone)]
+enum Symmetry {
+    // Neighbour orders to try, as indices into the cell's neighbours
+    Orders(Vec<Vec<usize>>),
+    Permute,
+}
+
+#[derive(Debug, Clone)]
+struct RuleTable {
+    variables: Vec<Vec<bool>>,
+    symmetry: Symmetry,
+    // Centre, neighbours and output
+    transitions: Vec<Vec<Term>>,
+}
+
+#[derive(Debug, Clone)]
+struct RuleTree {
+    // Each node lists one entry per state; level 1 entries are next states,
+    // higher ones are indices of lower nodes
+    nodes: Vec<Vec<usize>>,
+    // Neighbour index looked up at each level from the root, then the centre
+    order: Vec<Option<usize>>,
+}
+
+#[derive(Debug, Clone)]
+enum RuleLogic {
+    Table(RuleTable),
+    Tree(RuleTree),
+}
+
+// A rule loaded from a Golly `.rule` file
+#[derive(Debug, Clone)]
+pub struct GollyRule {
+    name: String,
+    states: usize,
+    neighborhood: GollyNeighborhood,
+    logic: RuleLogic,
+    colors: BTreeMap<u8, (u8, u8, u8)>,
+}
+
+fn symmetry_orders(neighborhood: GollyNeighborhood, symmetry: &str) -> Option<Symmetry> {
+    let n = neighborhood.neighbors();
+    let rotations = |count: usize| -> Vec<Vec<usize>> {
+        (0..count)
+            .map(|r| (0..n).map(|k| (k + r * n / count) % n).collect())
+            .collect()
+    };
+    let reflect = |order: &Vec<usize>| -> Vec<usize> {
+        match neighborhood {
+            GollyNeighborhood::OneDimensional => vec![order[1], order[0]],
+            _ => (0..n).map(|k| order[(n - k) % n]).collect(),
+        }
+    };
+    let with_reflections = |orders: Vec<Vec<usize>>| -> Vec<Vec<usize>> {
+        let reflected: Vec<Vec<usize>> = orders.iter().map(reflect).collect();
+        orders.into_iter().chain(reflected).collect()
+    };
+
+    let orders = match (neighborhood, symmetry) {
+        (_, "none") => rotations(1),
+        (_, "permute") => return Some(Symmetry::Permute),
+        (GollyNeighborhood::OneDimensional, "reflect") => with_reflections(rotations(1)),
+        (GollyNeighborhood::OneDimensional, _) => return None,
+        (_, "reflect_horizontal") => with_reflections(rotations(1)),
+        (GollyNeighborhood::Moore, "rotate8") | (GollyNeighborhood::Hexagonal, "rotate6") => {
+            rotations(n)
+        }
+        (GollyNeighborhood::Moore, "rotate8reflect")
+        | (GollyNeighborhood::Hexagonal, "rotate6reflect") => with_reflections(rotations(n)),
+        (GollyNeighborhood::Moore | GollyNeighborhood::VonNeumann, "rotate4") => rotations(4),
+        (GollyNeighborhood::Moore | GollyNeighborhood::VonNeumann, "rotate4reflect") => {
+            with_reflections(rotations(4))
+        }
+        (GollyNeighborhood::Hexagonal, "rotate2") => rotations(2),
+        (GollyNeighborhood::Hexagonal, "rotate3") => rotations(3),
+        _ => return None,
+    };
+
+    Some(Symmetry::Orders(orders))
+}
+
+fn bind(term: Term, value: u8, variables: &[Vec<bool>], bound: &mut [Option<u8>]) -> bool {
+    match term {
+        Term::State(state) => state == value,
+        Term::Variable(v) => match bound[v] {
+            Some(b) => b == value,
+            None if variables[v].get(value as usize) == Some(&true) => {
+                bound[v] = Some(value);
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+// Assigns the remaining neighbour values to the remaining terms in any order
+fn bind_permuted(
+    terms: &[Term],
+    values: &mut Vec<u8>,
+    variables: &[Vec<bool>],
+    bound: &mut Vec<Option<u8>>,
+) -> bool {
+    let Some((&term, rest)) = terms.split_first() else {
+        return true;
+    };
+
+    for k in 0..values.len() {
+        // Equal values give the same outcome, so only try each one once
+        if values[..k].contains(&values[k]) {
+            continue;
+        }
+
+        let saved = bound.clone();
+        let value = values.remove(k);
+        if bind(term, value, variables, bound) && bind_permuted(rest, values, variables, bound) {
+            return true;
+        }
+        values.insert(k, value);
+        *bound = saved;
+    }
+
+    false
+}
+
+impl RuleTable {
+    fn next_state(&self, center: u8, neighbors: &[u8]) -> Option<u8> {
+        let n = neighbors.len();
+
+        for transition in &self.transitions {
+            let output = transition[n + 1];
+            let mut found = None;
+
+            match &self.symmetry {
+                Symmetry::Orders(orders) => {
+                    for order in orders {
+                        let mut bound = vec![None; self.variables.len()];
+                        let matched = bind(transition[0], center, &self.variables, &mut bound)
+                            && order.iter().enumerate().all(|(k, &o)| {
+                                bind(transition[k + 1], neighbors[o], &self.variables, &mut bound)
+                            });
+                        if matched {
+                            found = Some(bound);
+                            break;
+                        }
+                    }
+                }
+                Symmetry::Permute => {
+                    let mut bound = vec![None; self.variables.len()];
+                    let mut values = neighbors.to_vec();
+                    if bind(transition[0], center, &self.variables, &mut bound)
+                        && bind_permuted(
+                            &transition[1..=n],
+                            &mut values,
+                            &self.variables,
+                            &mut bound,
+                        )
+                    {
+                        found = Some(bound);
+                    }
+                }
+            }
+
+            if let Some(bound) = found {
+                return match output {
+                    Term::State(state) => Some(state),
+                    Term::Variable(v) => bound[v],
+                };
+            }
+        }
+
+        None
+    }
+}
+
+impl RuleTree {
+    fn next_state(&self, center: u8, neighbors: &[u8]) -> u8 {
+        let mut node = self.nodes.len() - 1;
+        for &position in &self.order {
+            let value = match position {
+                Some(k) => neighbors[k],
+                None => center,
+            };
+            node = self.nodes[node][value as usize];
+        }
+        node as u8
+    }
+}
+
+impl GollyRule {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn states(&self) -> usize {
+        self.states
+    }
+
+    pub fn neighborhood(&self) -> GollyNeighborhood {
+        self.neighborhood
+    }
+
+    // Colour given by the `@COLORS` section, if any
+    pub fn color(&self, state: u8) -> Option<(u8, u8, u8)> {
+        self.colors.get(&state).copied()
+    }
+
+    // Applies the rule to one cell; `neighbors` follow the order documented
+    // on `GollyNeighborhood`. Cells no transition matches keep their state.
+    pub fn next_state(&self, center: u8, neighbors: &[u8]) -> u8 {
+        match &self.logic {
+            RuleLogic::Table(table) => table.next_state(center, neighbors).unwrap_or(center),
+            RuleLogic::Tree(tree) => tree.next_state(center, neighbors),
+        }
+    }
+
+    fn valid_world<'a>(&self, mut cells: impl Iterator<Item = &'a u8>) -> bool {
+        cells.all(|&cell| (cell as usize) < self.states)
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("").trim()
+}
+
+fn parse_state(token: &str, line: usize, states: usize) -> Result<u8, GollyRuleError> {
+    token
+        .parse::<usize>()
+        .ok()
+        .filter(|&s| s < states)
+        .map(|s| s as u8)
+        .ok_or(rule_error(line, GollyRuleErrorKind::InvalidState))
+}
+
+fn parse_table(
+    lines: &[(usize, &str)],
+    end: usize,
+) -> Result<(usize, GollyNeighborhood, RuleTable), GollyRuleError> {
+    let mut states = None;
+    let mut neighborhood = None;
+    let mut symmetry = "none".to_string();
+    let mut variable_names: HashMap<String, usize> = HashMap::new();
+    let mut table = RuleTable {
+        variables: Vec::new(),
+        symmetry: Symmetry::Orders(Vec::new()),
+        transitions: Vec::new(),
+    };
+    let mut raw_transitions = Vec::new();
+
+    for &(number, line) in lines {
+        let line = strip_comment(line);
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "n_states" => {
+                    states = Some(
+                        value
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|&s| (2..=256).contains(&s))
+                            .ok_or(rule_error(number, GollyRuleErrorKind::InvalidState))?,
+                    )
+                }
+                "neighborhood" => {
+                    neighborhood = Some(match value {
+                        "Moore" => GollyNeighborhood::Moore,
+                        "vonNeumann" => GollyNeighborhood::VonNeumann,
+                        "hexagonal" => GollyNeighborhood::Hexagonal,
+                        "oneDimensional" => GollyNeighborhood::OneDimensional,
+                        _ => {
+                            return Err(rule_error(
+                                number,
+                                GollyRuleErrorKind::UnsupportedNeighborhood,
+                            ))
+                        }
+                    })
+                }
+                "symmetries" => symmetry = value.to_string(),
+                _ => return Err(rule_error(number, GollyRuleErrorKind::Syntax)),
+            }
+            continue;
+        }
+
+        let states = states.ok_or(rule_error(number, GollyRuleErrorKind::MissingRule))?;
+
+        if let Some(definition) = line.strip_prefix("var ") {
+            let (name, members) = definition
+                .split_once('=')
+                .ok_or(rule_error(number, GollyRuleErrorKind::Syntax))?;
+            // Either a braced list of states and variables, or another
+            // variable's name on its own
+            let members = members.trim();
+            let members = match members.strip_prefix('{') {
+                Some(m) => m
+                    .strip_suffix('}')
+                    .ok_or(rule_error(number, GollyRuleErrorKind::Syntax))?,
+                None => members,
+            };
+
+            let mut set = vec![false; states];
+            for member in members.split(',').map(str::trim) {
+                match variable_names.get(member) {
+                    Some(&v) => {
+                        for (s, &included) in table.variables[v].iter().enumerate() {
+                            set[s] |= included;
+                        }
+                    }
+                    None => set[parse_state(member, number, states)? as usize] = true,
+                }
+            }
+
+            variable_names.insert(name.trim().to_string(), table.variables.len());
+            table.variables.push(set);
+            continue;
+        }
+
+        raw_transitions.push((number, states, line));
+    }
+
+    let states = states.ok_or(rule_error(end, GollyRuleErrorKind::MissingRule))?;
+    let neighborhood = neighborhood.ok_or(rule_error(end, GollyRuleErrorKind::MissingRule))?;
+    table.symmetry = symmetry_orders(neighborhood, &symmetry)
+        .ok_or(rule_error(end, GollyRuleErrorKind::UnsupportedSymmetry))?;
+
+    for (number, states, line) in raw_transitions {
+        let tokens: Vec<String> = if line.contains(',') {
+            line.split(',').map(|t| t.trim().to_string()).collect()
+        } else if line.chars().all(|c| c.is_ascii_digit()) {
+            line.chars().map(|c| c.to_string()).collect()
+        } else {
+            return Err(rule_error(number, GollyRuleErrorKind::Syntax));
+        };
+
+        if tokens.len() != neighborhood.neighbors() + 2 {
+            return Err(rule_error(number, GollyRuleErrorKind::Syntax));
+        }
+
+        let terms = tokens
+            .iter()
+            .map(|token| match variable_names.get(token.as_str()) {
+                Some(&v) => Ok(Term::Variable(v)),
+                None if token.chars().all(|c| c.is_ascii_digit()) => {
+                    parse_state(token, number, states).map(Term::State)
+                }
+                None => Err(rule_error(number, GollyRuleErrorKind::UnknownVariable)),
+            })
+            .collect::<Result<Vec<Term>, GollyRuleError>>()?;
+
+        // The output can only use variables bound by the inputs
+        let (output, inputs) = terms.split_last().expect("Transitions are never empty");
+        if let Term::Variable(_) = output {
+            if !inputs.contains(output) {
+                return Err(rule_error(number, GollyRuleErrorKind::UnknownVariable));
+            }
+        }
+
+        table.transitions.push(terms);
+    }
+
+    Ok((states, neighborhood, table))
+}
+
+fn parse_tree(
+    lines: &[(usize, &str)],
+    end: usize,
+) -> Result<(usize, GollyNeighborhood, RuleTree), GollyRuleError> {
+    let mut states = None;
+    let mut neighbors = None;
+    let mut nodes: Vec<Vec<usize>> = Vec::new();
+    let mut levels: Vec<usize> = Vec::new();
+
+    for &(number, line) in lines {
+        let line = strip_comment(line);
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| rule_error(number, GollyRuleErrorKind::Syntax))?;
+            match key.trim() {
+                "num_states" if (2..=256).contains(&value) => states = Some(value),
+                "num_neighbors" if value == 4 || value == 8 => neighbors = Some(value),
+                "num_nodes" => {}
+                "num_states" => return Err(rule_error(number, GollyRuleErrorKind::InvalidState)),
+                "num_neighbors" => {
+                    return Err(rule_error(
+                        number,
+                        GollyRuleErrorKind::UnsupportedNeighborhood,
+                    ))
+                }
+                _ => return Err(rule_error(number, GollyRuleErrorKind::Syntax)),
+            }
+            continue;
+        }
+
+        let states = states.ok_or(rule_error(number, GollyRuleErrorKind::MissingRule))?;
+        let values = line
+            .split_whitespace()
+            .map(|v| v.parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| rule_error(number, GollyRuleErrorKind::Syntax))?;
+
+        let (&level, entries) = values
+            .split_first()
+            .ok_or(rule_error(number, GollyRuleErrorKind::Syntax))?;
+        let valid = entries.len() == states
+            && level >= 1
+            && entries.iter().all(|&e| match level {
+                1 => e < states,
+                _ => e < nodes.len() && levels[e] == level - 1,
+            });
+        if !valid {
+            return Err(rule_error(number, GollyRuleErrorKind::InvalidTree));
+        }
+
+        nodes.push(entries.to_vec());
+        levels.push(level);
+    }
+
+    let states = states.ok_or(rule_error(end, GollyRuleErrorKind::MissingRule))?;
+    let neighbors = neighbors.ok_or(rule_error(end, GollyRuleErrorKind::MissingRule))?;
+    if levels.last() != Some(&(neighbors + 1)) {
+        return Err(rule_error(end, GollyRuleErrorKind::InvalidTree));
+    }
+
+    // Golly walks Moore trees as NW, NE, SW, SE, N, W, E, S and then the
+    // centre, and von Neumann trees as N, W, E, S and the centre
+    let (neighborhood, order) = match neighbors {
+        8 => (
+            GollyNeighborhood::Moore,
+            vec![
+                Some(7),
+                Some(1),
+                Some(5),
+                Some(3),
+                Some(0),
+                Some(6),
+                Some(2),
+                Some(4),
+                None,
+            ],
+        ),
+        _ => (
+            GollyNeighborhood::VonNeumann,
+            vec![Some(0), Some(3), Some(1), Some(2), None],
+        ),
+    };
+
+    Ok((states, neighborhood, RuleTree { nodes, order }))
+}
+
+// Lines are `state r g b`, or `first r g b last r g b` for a gradient over
+// the states in between
+fn parse_colors(lines: &[(usize, &str)]) -> Result<BTreeMap<u8, (u8, u8, u8)>, GollyRuleError> {
+    let mut colors = BTreeMap::new();
+
+    for &(number, line) in lines {
+        let line = strip_comment(line);
+        if line.is_empty() {
+            continue;
+        }
+
+        let values = line
+            .split_whitespace()
+            .map(|v| v.parse::<u8>())
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| rule_error(number, GollyRuleErrorKind::Syntax))?;
+
+        match values[..] {
+            [state, r, g, b] => {
+                colors.insert(state, (r, g, b));
+            }
+            [first, r0, g0, b0, last, r1, g1, b1] if first <= last => {
+                let span = (last - first).max(1) as f64;
+                for state in first..=last {
+                    let t = (state - first) as f64 / span;
+                    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+                    colors.insert(state, (mix(r0, r1), mix(g0, g1), mix(b0, b1)));
+                }
+            }
+            _ => return Err(rule_error(number, GollyRuleErrorKind::Syntax)),
+        }
+    }
+
+    Ok(colors)
+}
+
+// Parses a Golly `.rule` file with an `@TABLE` or `@TREE` section and an
+// optional `@COLORS` section. Other sections such as `@ICONS` are skipped.
+pub fn parse_golly_rule(text: &str) -> Result<GollyRule, GollyRuleError> {
+    let mut name = String::new();
+    let mut sections: HashMap<&str, Vec<(usize, &str)>> = HashMap::new();
+    let mut current = "";
+    let end = text.lines().count();
+
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(header) = trimmed.strip_prefix('@') {
+            let mut parts = header.split_whitespace();
+            current = parts.next().unwrap_or("");
+            if current == "RULE" {
+                name = parts.collect::<Vec<&str>>().join(" ");
+            }
+            sections.entry(current).or_default();
+        } else {
+            sections.entry(current).or_default().push((i + 1, line));
+        }
+    }
+
+    let (states, neighborhood, logic) = match (sections.get("TABLE"), sections.get("TREE")) {
+        (Some(lines), _) => {
+            let (states, neighborhood, table) = parse_table(lines, end)?;
+            (states, neighborhood, RuleLogic::Table(table))
+        }
+        (None, Some(lines)) => {
+            let (states, neighborhood, tree) = parse_tree(lines, end)?;
+            (states, neighborhood, RuleLogic::Tree(tree))
+        }
+        (None, None) => return Err(rule_error(end, GollyRuleErrorKind::MissingRule)),
+    };
+
+    let colors = match sections.get("COLORS") {
+        Some(lines) => parse_colors(lines)?,
+        None => BTreeMap::new(),
+    };
+
+    Ok(GollyRule {
+        name,
+        states,
+        neighborhood,
+        logic,
+        colors,
+    })
+}
+
+// Cells beyond the border are in state 0
+fn golly_neighbors(world: &[Vec<u8>], i: usize, j: usize, wrapping: bool) -> Neighbors2d<u8, 3, 3> {
+    let height = world.len() as isize;
+    let width = world[0].len() as isize;
+
+    let mut neighbors = [[0u8; 3]; 3];
+
+    for (x, row) in neighbors.iter_mut().enumerate() {
+        for (y, cell) in row.iter_mut().enumerate() {
+            let n_i = i as isize + x as isize - 1;
+            let n_j = j as isize + y as isize - 1;
+
+            if wrapping {
+                *cell = world[n_i.rem_euclid(height) as usize][n_j.rem_euclid(width) as usize];
+            } else if n_i >= 0 && n_i < height && n_j >= 0 && n_j < width {
+                *cell = world[n_i as usize][n_j as usize];
+            }
+        }
+    }
+
+    Neighbors2d::Neighborhood(neighbors)
+}
+
+// Runs a 2D Golly rule. Results are cached per neighbourhood, so tables with
+// many transitions only pay for the lookup once.
+#[allow(non_snake_case)]
+pub fn GollyCellularAutomaton(
+    world: Vec<Vec<u8>>,
+    rule: GollyRule,
+    wrapping: bool,
+) -> Result<CellularAutomaton2d<u8, 3, 3>, CellularAutomatonWorldSizeError> {
+    let width = world.first().map_or(0, |row| row.len());
+    if rule.neighborhood == GollyNeighborhood::OneDimensional
+        || width == 0
+        || world.iter().any(|row| row.len() != width)
+        || !rule.valid_world(world.iter().flatten())
+    {
+        return Err(CellularAutomatonWorldSizeError);
+    }
+
+    let cache: RefCell<HashMap<[[u8; 3]; 3], u8>> = RefCell::new(HashMap::new());
+    let offsets = rule.neighborhood.offsets();
+
+    CellularAutomaton2d::<u8, 3, 3>::new(
+        world,
+        move |block: [[u8; 3]; 3]| {
+            if let Some(&next) = cache.borrow().get(&block) {
+                return next;
+            }
+            let neighbors: Vec<u8> = offsets.iter().map(|&(x, y)| block[x][y]).collect();
+            let next = rule.next_state(block[1][1], &neighbors);
+            cache.borrow_mut().insert(block, next);
+            next
+        },
+        move |world, i, j| golly_neighbors(world, i, j, wrapping),
+    )
+}
+
+#[allow(non_snake_case)]
+pub fn GollyCellularAutomaton1d(
+    world: Vec<u8>,
+    rule: GollyRule,
+    wrapping: bool,
+) -> Result<CellularAutomaton1d<u8, 3>, CellularAutomatonWorldSizeError> {
+    if rule.neighborhood != GollyNeighborhood::OneDimensional || !rule.valid_world(world.iter()) {
+        return Err(CellularAutomatonWorldSizeError);
+    }
+
+    CellularAutomaton1d::<u8, 3>::new(
+        world,
+        move |[w, c, e]: [u8; 3]| rule.next_state(c, &[w, e]),
+        move |world: &[u8], i| {
+            let n = world.len();
+            let (w, e) = if wrapping {
+                (world[(i + n - 1) % n], world[(i + 1) % n])
+            } else {
+                (
+                    if i > 0 { world[i - 1] } else { 0 },
+                    world.get(i + 1).copied().unwrap_or(0),
+                )
+            };
+            Neighbors1d::Neighborhood([w, world[i], e])
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::CellularAutomaton;
+    use crate::conway::ConwayCellularAutomaton;
+    use crate::elementary::ElementaryCellularAutomaton;
+    use crate::wireworld::{parse_wireworld, WireworldCell, WireworldCellularAutomaton};
+
+    const LIFE_TABLE: &str = "@RULE Life
+# B3/S23, written out with permute so every order of neighbours matches
+@TABLE
+n_states:2
+neighborhood:Moore
+symmetries:permute
+var a={0,1}
+var b=a
+var c=a
+var d=a
+var e=a
+0,1,1,1,0,0,0,0,0,1
+1,0,0,0,0,0,0,0,0,0
+1,1,0,0,0,0,0,0,0,0
+1,1,1,1,1,a,b,c,d,0
+
+@COLORS
+0 48 48 48
+1 255 255 0
+";
+
+    const WIREWORLD_TABLE: &str = "@RULE WireWorld
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,2,3}
+var i={0,2,3}
+var j={0,2,3}
+var k={0,2,3}
+var l={0,2,3}
+var m={0,2,3}
+var n={0,2,3}
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+3,1,h,i,j,k,l,m,n,1
+3,1,1,i,j,k,l,m,n,1
+";
+
+    fn life_world() -> Vec<Vec<bool>> {
+        let mut world = vec![vec![false; 10]; 10];
+        for &(i, j) in &[
+            (1, 2),
+            (2, 3),
+            (3, 1),
+            (3, 2),
+            (3, 3),
+            (6, 6),
+            (6, 7),
+            (7, 6),
+        ] {
+            world[i][j] = true;
+        }
+        world
+    }
+
+    fn to_u8(world: &[Vec<bool>]) -> Vec<Vec<u8>> {
+        world
+            .iter()
+            .map(|row| row.iter().map(|&c| c as u8).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_table_matches_conway() {
+        let rule = parse_golly_rule(LIFE_TABLE).expect("Parsing failed");
+        assert_eq!(rule.name(), "Life");
+        assert_eq!(rule.states(), 2);
+        assert_eq!(rule.neighborhood(), GollyNeighborhood::Moore);
+        assert_eq!(rule.color(1), Some((255, 255, 0)));
+
+        let mut conway = ConwayCellularAutomaton(life_world(), true).expect("Construction failed");
+        let mut golly =
+            GollyCellularAutomaton(to_u8(&life_world()), rule, true).expect("Construction failed");
+
+        for _ in 0..20 {
+            conway.step();
+            golly.step();
+            assert_eq!(golly.world(), to_u8(&conway.world()));
+        }
+    }
+
+    #[test]
+    fn test_table_matches_wireworld() {
+        let cells = parse_wireworld(" tH \n#  #####\n ## ").expect("Parsing failed");
+        let encode = |world: &Vec<Vec<WireworldCell>>| -> Vec<Vec<u8>> {
+            world
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| match cell {
+                            WireworldCell::Empty => 0,
+                            WireworldCell::Head => 1,
+                            WireworldCell::Tail => 2,
+                            WireworldCell::Conductor => 3,
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        let rule = parse_golly_rule(WIREWORLD_TABLE).expect("Parsing failed");
+        let mut golly =
+            GollyCellularAutomaton(encode(&cells), rule, false).expect("Construction failed");
+        let mut wireworld = WireworldCellularAutomaton(cells).expect("Construction failed");
+
+        for _ in 0..12 {
+            golly.step();
+            wireworld.step();
+            assert_eq!(golly.world(), encode(&wireworld.world()));
+        }
+    }
+
+    #[test]
+    fn test_von_neumann_rotations() {
+        // A cell with a single 1 to its north turns into 2, in any rotation
+        let text =
+            "@RULE Turn\n@TABLE\nn_states:3\nneighborhood:vonNeumann\nsymmetries:rotate4\n010002\n";
+        let rule = parse_golly_rule(text).expect("Parsing failed");
+
+        assert_eq!(rule.next_state(0, &[1, 0, 0, 0]), 2);
+        assert_eq!(rule.next_state(0, &[0, 0, 1, 0]), 2);
+        assert_eq!(rule.next_state(0, &[1, 1, 0, 0]), 0);
+
+        let none = parse_golly_rule(&text.replace("rotate4", "none")).expect("Parsing failed");
+        assert_eq!(none.next_state(0, &[0, 0, 1, 0]), 0);
+    }
+
+    #[test]
+    fn test_reflection_and_bound_variables() {
+        // `x` must take the same value in both places it appears
+        let text = "@RULE Mirror
+@TABLE
+n_states:3
+neighborhood:Moore
+symmetries:reflect_horizontal
+var x={1,2}
+0,x,x,0,0,0,0,0,0,x
+";
+        let rule = parse_golly_rule(text).expect("Parsing failed");
+
+        assert_eq!(rule.next_state(0, &[2, 2, 0, 0, 0, 0, 0, 0]), 2);
+        assert_eq!(rule.next_state(0, &[1, 0, 0, 0, 0, 0, 0, 1]), 1);
+        assert_eq!(rule.next_state(0, &[1, 2, 0, 0, 0, 0, 0, 0]), 0);
+        assert_eq!(rule.next_state(0, &[0, 0, 1, 1, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn test_hexagonal_rule() {
+        let text = "@RULE Hex
+@TABLE
+n_states:2
+neighborhood:hexagonal
+symmetries:rotate6
+0,1,1,0,0,0,0,1
+";
+        let rule = parse_golly_rule(text).expect("Parsing failed");
+        let mut world = vec![vec![0u8; 3]; 3];
+        world[0][1] = 1;
+        world[2][2] = 1;
+        world[0][2] = 1;
+
+        // NE and SW are not hexagonal neighbours, so only N and SE count and
+        // they are not adjacent on the hexagon
+        let mut ca = GollyCellularAutomaton(world.clone(), rule.clone(), false)
+            .expect("Construction failed");
+        ca.step();
+        assert_eq!(ca.world()[1][1], 0);
+
+        world[2][2] = 0;
+        world[1][2] = 1;
+        let mut ca = GollyCellularAutomaton(world, rule, false).expect("Construction failed");
+        ca.step();
+        assert_eq!(ca.world()[1][1], 1);
+    }
+
+    #[test]
+    fn test_one_dimensional_rule_30() {
+        let text = "@RULE Rule30
+@TABLE
+n_states:2
+neighborhood:oneDimensional
+symmetries:none
+var a={0,1}
+# Rule 30 is left XOR (centre OR right)
+0,1,0,1
+0,0,1,1
+1,0,a,1
+1,1,a,0
+0,0,0,0
+";
+        let rule = parse_golly_rule(text).expect("Parsing failed");
+        let mut world = vec![0u8; 31];
+        world[15] = 1;
+
+        let mut golly = GollyCellularAutomaton1d(world, rule, true).expect("Construction failed");
+        let mut bool_world = vec![false; 31];
+        bool_world[15] = true;
+        let mut eca = ElementaryCellularAutomaton(bool_world, 30).expect("Construction failed");
+
+        for _ in 0..14 {
+            golly.step();
+            eca.step();
+            let expected: Vec<u8> = eca.world().iter().map(|&c| c as u8).collect();
+            assert_eq!(golly.world(), expected);
+        }
+    }
+
+    // Writes a Moore rule tree the way Golly's generator does, sharing equal
+    // nodes, so it can be checked against the rule it came from
+    fn build_tree(states: usize, rule: &dyn Fn(&[usize]) -> usize) -> String {
+        fn build(
+            prefix: &mut Vec<usize>,
+            states: usize,
+            depth: usize,
+            rule: &dyn Fn(&[usize]) -> usize,
+            nodes: &mut Vec<(usize, Vec<usize>)>,
+        ) -> usize {
+            let entries: Vec<usize> = (0..states)
+                .map(|s| {
+                    prefix.push(s);
+                    let entry = if depth == 1 {
+                        rule(prefix)
+                    } else {
+                        build(prefix, states, depth - 1, rule, nodes)
+                    };
+                    prefix.pop();
+                    entry
+                })
+                .collect();
+            let node = (depth, entries);
+            match nodes.iter().position(|n| *n == node) {
+                Some(k) => k,
+                None => {
+                    nodes.push(node);
+                    nodes.len() - 1
+                }
+            }
+        }
+
+        let mut nodes = Vec::new();
+        build(&mut Vec::new(), states, 9, rule, &mut nodes);
+
+        let mut text = format!(
+            "@RULE TreeLife\n@TREE\nnum_states={}\nnum_neighbors=8\nnum_nodes={}\n",
+            states,
+            nodes.len()
+        );
+        for (level, entries) in nodes {
+            let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+            text += &format!("{} {}\n", level, entries.join(" "));
+        }
+        text
+    }
+
+    #[test]
+    fn test_tree_matches_conway() {
+        // Inputs arrive as NW, NE, SW, SE, N, W, E, S, C
+        let tree = build_tree(2, &|cells| {
+            let live: usize = cells[..8].iter().sum();
+            (live == 3 || (cells[8] == 1 && live == 2)) as usize
+        });
+        let rule = parse_golly_rule(&tree).expect("Parsing failed");
+        assert_eq!(rule.neighborhood(), GollyNeighborhood::Moore);
+
+        let mut conway = ConwayCellularAutomaton(life_world(), true).expect("Construction failed");
+        let mut golly =
+            GollyCellularAutomaton(to_u8(&life_world()), rule, true).expect("Construction failed");
+
+        for _ in 0..20 {
+            conway.step();
+            golly.step();
+            assert_eq!(golly.world(), to_u8(&conway.world()));
+        }
+    }
+
+    #[test]
+    fn test_tree_neighbor_order() {
+        // The next state is whatever the north-west neighbour holds
+        let tree = build_tree(3, &|cells| cells[0]);
+        let rule = parse_golly_rule(&tree).expect("Parsing failed");
+
+        assert_eq!(rule.next_state(0, &[0, 0, 0, 0, 0, 0, 0, 2]), 2);
+        assert_eq!(rule.next_state(1, &[2, 2, 2, 2, 2, 2, 2, 0]), 0);
+    }
+
+    #[test]
+    fn test_color_gradient() {
+        let text = "@RULE G\n@TABLE\nn_states:5\nneighborhood:Moore\n@COLORS\n1 0 0 0 3 200 100 0\n4 1 2 3\n";
+        let rule = parse_golly_rule(text).expect("Parsing failed");
+
+        assert_eq!(rule.color(1), Some((0, 0, 0)));
+        assert_eq!(rule.color(2), Some((100, 50, 0)));
+        assert_eq!(rule.color(3), Some((200, 100, 0)));
+        assert_eq!(rule.color(4), Some((1, 2, 3)));
+        assert_eq!(rule.color(0), None);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let err = |text: &str| parse_golly_rule(text).expect_err("Parsing succeeded");
+
+        assert_eq!(err("@RULE Empty\n").kind, GollyRuleErrorKind::MissingRule);
+        assert_eq!(
+            err("@TABLE\nn_states:2\nneighborhood:Moore\n0,1,1\n"),
+            GollyRuleError {
+                line: 4,
+                kind: GollyRuleErrorKind::Syntax
+            }
+        );
+        assert_eq!(
+            err("@TABLE\nn_states:2\nneighborhood:Moore\n0,1,1,1,0,0,0,0,0,2\n").kind,
+            GollyRuleErrorKind::InvalidState
+        );
+        assert_eq!(
+            err("@TABLE\nn_states:2\nneighborhood:Moore\n0,q,1,1,0,0,0,0,0,1\n").kind,
+            GollyRuleErrorKind::UnknownVariable
+        );
+        assert_eq!(
+            err("@TABLE\nn_states:2\nneighborhood:triangular\n").kind,
+            GollyRuleErrorKind::UnsupportedNeighborhood
+        );
+        assert_eq!(
+            err("@TABLE\nn_states:2\nneighborhood:vonNeumann\nsymmetries:rotate8\n").kind,
+            GollyRuleErrorKind::UnsupportedSymmetry
+        );
+        assert_eq!(
+            err("@TREE\nnum_states=2\nnum_neighbors=4\nnum_nodes=1\n1 0 1\n").kind,
+            GollyRuleErrorKind::InvalidTree
+        );
+
+        let rule = parse_golly_rule(LIFE_TABLE).expect("Parsing failed");
+        assert!(GollyCellularAutomaton(vec![vec![2; 3]; 3], rule.clone(), true).is_err());
+        assert!(GollyCellularAutomaton1d(vec![0; 3], rule, true).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+#[derive(Debug, Clone)]
+pub struct EdgeListParseError {
+    pub line: usize,
+}
+
+type GraphEvolutionFn<CellType> = Box<dyn Fn(&CellType, &[CellType]) -> CellType>;
+
+pub struct GraphCellularAutomaton<CellType: Clone> {
+    world: Vec<CellType>,
+    adjacency: Vec<Vec<usize>>,
+    generation: usize,
+    evolvution_fn: GraphEvolutionFn<CellType>,
+}
+
+impl<CellType: Clone> GraphCellularAutomaton<CellType> {
+    pub fn new(
+        world: Vec<CellType>,
+        adjacency: Vec<Vec<usize>>,
+        evolvution_fn: impl Fn(&CellType, &[CellType]) -> CellType + 'static,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        if world.len() != adjacency.len() || adjacency.iter().flatten().any(|&n| n >= world.len()) {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            adjacency,
+            generation: 0,
+            evolvution_fn: Box::new(evolvution_fn),
+        })
+    }
+
+    pub fn from_edge_list(
+        world: Vec<CellType>,
+        edges: &[(usize, usize)],
+        evolvution_fn: impl Fn(&CellType, &[CellType]) -> CellType + 'static,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        let mut adjacency = vec![Vec::new(); world.len()];
+
+        for &(a, b) in edges {
+            if a >= world.len() || b >= world.len() {
+                return Err(CellularAutomatonWorldSizeError);
+            }
+            if a == b || adjacency[a].contains(&b) {
+                continue;
+            }
+            adjacency[a].push(b);
+            adjacency[b].push(a);
+        }
+
+        Self::new(world, adjacency, evolvution_fn)
+    }
+
+    pub fn neighbors(&self, cell: usize) -> &[usize] {
+        &self.adjacency[cell]
+    }
+
+    pub fn adjacency(&self) -> &Vec<Vec<usize>> {
+        &self.adjacency
+    }
+}
+
+impl<CellType: Clone> CellularAutomaton for GraphCellularAutomaton<CellType> {
+    type WorldType = Vec<CellType>;
+
+    fn step(&mut self) -> usize {
+        let mut states = Vec::new();
+
+        self.world = self
+            .adjacency
+            .iter()
+            .enumerate()
+            .map(|(i, neighbors)| {
+                states.clear();
+                states.extend(neighbors.iter().map(|&n| self.world[n].clone()));
+                (self.evolvution_fn)(&self.world[i], &states)
+            })
+            .collect();
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// Parses one undirected edge per line as two whitespace separated cell
+// indices. Blank lines and lines starting with `#` are ignored.
+pub fn parse_edge_list(input: &str) -> Result<Vec<(usize, usize)>, EdgeListParseError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_no, line)| {
+            let mut fields = line.split_whitespace().map(str::parse::<usize>);
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(Ok(a)), Some(Ok(b)), None) => Ok((a, b)),
+                _ => Err(EdgeListParseError { line: line_no }),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn majority(cell: &bool, neighbors: &[bool]) -> bool {
+        let live = neighbors.iter().filter(|&&n| n).count() + *cell as usize;
+        live * 2 > neighbors.len() + 1
+    }
+
+    #[test]
+    fn test_graph_varying_degree() {
+        // A star: the hub sees three cells, every leaf sees only the hub
+        let edges = [(0, 1), (0, 2), (0, 3)];
+        let mut ca = GraphCellularAutomaton::from_edge_list(
+            vec![false, true, true, false],
+            &edges,
+            |_, neighbors: &[bool]| neighbors.iter().filter(|&&n| n).count() == 1,
+        )
+        .expect("Construction failed");
+
+        assert_eq!(ca.neighbors(0), &[1, 2, 3]);
+        assert_eq!(ca.neighbors(2), &[0]);
+
+        ca.step();
+
+        assert_eq!(ca.world(), vec![false, false, false, false]);
+        assert_eq!(ca.age(), 1);
+    }
+
+    #[test]
+    fn test_graph_majority_ring() {
+        let edges: Vec<(usize, usize)> = (0..6).map(|i| (i, (i + 1) % 6)).collect();
+        let mut ca = GraphCellularAutomaton::from_edge_list(
+            vec![true, true, false, true, false, false],
+            &edges,
+            majority,
+        )
+        .expect("Construction failed");
+
+        ca.step();
+
+        assert_eq!(ca.world(), vec![true, true, true, false, false, false]);
+    }
+
+    #[test]
+    fn test_graph_invalid_adjacency() {
+        let result = GraphCellularAutomaton::new(vec![false; 2], vec![vec![1], vec![2]], majority);
+        assert!(result.is_err());
+
+        let result = GraphCellularAutomaton::new(vec![false; 2], vec![vec![1]], majority);
+        assert!(result.is_err());
+
+        let result = GraphCellularAutomaton::from_edge_list(vec![false; 2], &[(0, 5)], majority);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_edge_list() {
+        let edges = parse_edge_list("# triangle\n0 1\n1 2\n\n2 0\n").expect("Parse failed");
+        assert_eq!(edges, vec![(0, 1), (1, 2), (2, 0)]);
+
+        let err = parse_edge_list("0 1\n1 x\n").unwrap_err();
+        assert_eq!(err.line, 2);
+
+        assert!(parse_edge_list("0 1 2\n").is_err());
+    }
+}
@@ -0,0 +1,577 @@
+use std::io::{self, Read};
+
+use crate::render::{luminance, Palette, Rgb};
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Png(png::DecodingError),
+    // Neither Netpbm nor PNG, going by the first bytes
+    UnknownFormat,
+    // A format that is recognised but can't be read, such as PAM
+    UnsupportedFormat(String),
+    // A Netpbm header that can't be read, and why
+    InvalidHeader(String),
+    // Fewer pixels than the header promises
+    Truncated,
+    // A Netpbm sample above the maximum value or not a number
+    InvalidPixel {
+        x: usize,
+        y: usize,
+    },
+    // An image with no pixels
+    Empty,
+    // Image sides that aren't a whole number of cells
+    CellSize {
+        width: usize,
+        height: usize,
+        cell_size: usize,
+    },
+    // A colour with no state in the palette
+    UnknownColor {
+        color: Rgb,
+        x: usize,
+        y: usize,
+    },
+}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for ImportError {
+    fn from(err: png::DecodingError) -> Self {
+        ImportError::Png(err)
+    }
+}
+
+// A decoded image, whatever its format, as RGB pixels in row-major order
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+// Reads whitespace separated header fields, skipping `#` comments
+struct Tokens<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn skip_space(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'#' {
+                while self.data.get(self.position).is_some_and(|&b| b != b'\n') {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> Option<&'a [u8]> {
+        self.skip_space();
+        let start = self.position;
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.position += 1;
+        }
+        (self.position > start).then(|| &self.data[start..self.position])
+    }
+
+    fn number(&mut self, field: &str) -> Result<usize, ImportError> {
+        self.token()
+            .and_then(|token| std::str::from_utf8(token).ok()?.parse().ok())
+            .ok_or_else(|| ImportError::InvalidHeader(format!("missing or invalid {}", field)))
+    }
+}
+
+fn decode_netpbm(data: &[u8]) -> Result<Image, ImportError> {
+    let kind = data[1];
+    let mut tokens = Tokens { data, position: 2 };
+    let width = tokens.number("width")?;
+    let height = tokens.number("height")?;
+    let max = match kind {
+        b'1' | b'4' => 1,
+        _ => tokens.number("maximum value")?,
+    };
+    if !(1..=65535).contains(&max) {
+        return Err(ImportError::InvalidHeader(format!(
+            "maximum value {} is not between 1 and 65535",
+            max
+        )));
+    }
+    if width == 0 || height == 0 {
+        return Err(ImportError::Empty);
+    }
+
+    let channels = if matches!(kind, b'3' | b'6') { 3 } else { 1 };
+    let count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or_else(|| ImportError::InvalidHeader("image too large".into()))?;
+    let invalid = |sample: usize| ImportError::InvalidPixel {
+        x: sample / channels % width,
+        y: sample / channels / width,
+    };
+
+    // Binary rasters start after the single whitespace byte ending the header.
+    // Their size is checked before anything is allocated, so a header
+    // claiming a huge image cannot exhaust memory; plain samples take at
+    // least a byte each.
+    let raster = data.get(tokens.position + 1..).unwrap_or_default();
+    let raster_size = match kind {
+        b'4' => width.div_ceil(8).checked_mul(height),
+        b'5' | b'6' if max > 255 => count.checked_mul(2),
+        b'5' | b'6' => Some(count),
+        _ => Some(0),
+    }
+    .ok_or(ImportError::Truncated)?;
+    if raster.len() < raster_size {
+        return Err(ImportError::Truncated);
+    }
+    let mut samples = Vec::with_capacity(count.min(data.len()));
+    match kind {
+        // Plain bitmaps need no space between their digits
+        b'1' => {
+            while samples.len() < count {
+                tokens.skip_space();
+                match tokens.data.get(tokens.position) {
+                    Some(b'0') => samples.push(0),
+                    Some(b'1') => samples.push(1),
+                    Some(_) => return Err(invalid(samples.len())),
+                    None => break,
+                }
+                tokens.position += 1;
+            }
+        }
+        b'2' | b'3' => {
+            while samples.len() < count {
+                match tokens.token() {
+                    Some(token) => samples.push(
+                        std::str::from_utf8(token)
+                            .ok()
+                            .and_then(|token| token.parse().ok())
+                            .ok_or_else(|| invalid(samples.len()))?,
+                    ),
+                    None => break,
+                }
+            }
+        }
+        b'4' => {
+            for row in raster.chunks_exact(width.div_ceil(8)).take(height) {
+                samples.extend((0..width).map(|x| (row[x / 8] >> (7 - x % 8) & 1) as usize));
+            }
+        }
+        _ => {
+            if max < 256 {
+                samples.extend(raster.iter().take(count).map(|&s| s as usize));
+            } else {
+                samples.extend(
+                    raster
+                        .chunks_exact(2)
+                        .take(count)
+                        .map(|s| u16::from_be_bytes([s[0], s[1]]) as usize),
+                );
+            }
+        }
+    }
+
+    if samples.len() < count {
+        return Err(ImportError::Truncated);
+    }
+    if let Some(sample) = samples.iter().position(|&s| s > max) {
+        return Err(invalid(sample));
+    }
+
+    // Bitmaps have 1 for black, the other formats 0
+    let level = |sample: usize| match kind {
+        b'1' | b'4' => 255 - 255 * sample as u8,
+        _ => ((sample * 255 + max / 2) / max) as u8,
+    };
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|pixel| match *pixel {
+            [r, g, b] => [level(r), level(g), level(b)],
+            [grey] => [level(grey); 3],
+            _ => unreachable!("Pixels have one or three samples"),
+        })
+        .collect();
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn decode_png(data: &[u8]) -> Result<Image, ImportError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    if width == 0 || height == 0 {
+        return Err(ImportError::Empty);
+    }
+
+    // Transparent pixels are drawn over white
+    let over_white = |color: Rgb, alpha: u8| {
+        color.map(|c| ((c as u32 * alpha as u32 + 255 * (255 - alpha as u32)) / 255) as u8)
+    };
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => {
+            return Err(ImportError::UnsupportedFormat("indexed PNG".into()))
+        }
+    };
+    let pixels = buffer
+        .chunks(info.line_size)
+        .take(height)
+        .flat_map(|row| row[..width * channels].chunks_exact(channels))
+        .map(|pixel| match *pixel {
+            [grey] => [grey; 3],
+            [grey, alpha] => over_white([grey; 3], alpha),
+            [r, g, b] => [r, g, b],
+            [r, g, b, alpha] => over_white([r, g, b], alpha),
+            _ => unreachable!("Pixels have one to four samples"),
+        })
+        .collect();
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn decode(mut reader: impl Read) -> Result<Image, ImportError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    match data.as_slice() {
+        [0x89, b'P', b'N', b'G', ..] => decode_png(&data),
+        [b'P', b'1'..=b'6', ..] => decode_netpbm(&data),
+        [b'P', b'7', ..] => Err(ImportError::UnsupportedFormat("PAM".into())),
+        _ => Err(ImportError::UnknownFormat),
+    }
+}
+
+// Reads initial worlds from PBM, PGM, PPM or PNG images, plain or binary
+// Netpbm alike
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageLoader {
+    threshold: u8,
+    cell_size: usize,
+    nearest: bool,
+}
+
+impl Default for ImageLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageLoader {
+    pub fn new() -> Self {
+        Self {
+            threshold: 128,
+            cell_size: 1,
+            nearest: false,
+        }
+    }
+
+    // Pixels with a grey level below `threshold` are live cells in boolean
+    // worlds
+    pub fn with_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    // Each cell is a square of `size` pixels, read at its centre, as drawn
+    // by `WorldRenderer::with_cell_size`
+    pub fn with_cell_size(mut self, size: usize) -> Self {
+        self.cell_size = size.max(1);
+        self
+    }
+
+    // Colours not in the palette are read as the state with the closest
+    // colour rather than being an error
+    pub fn with_nearest_color(mut self, nearest: bool) -> Self {
+        self.nearest = nearest;
+        self
+    }
+
+    fn cells<T>(
+        &self,
+        image: &Image,
+        mut cell: impl FnMut(Rgb, usize, usize) -> Result<T, ImportError>,
+    ) -> Result<Vec<Vec<T>>, ImportError> {
+        let size = self.cell_size;
+        if (image.width % size, image.height % size) != (0, 0) {
+            return Err(ImportError::CellSize {
+                width: image.width,
+                height: image.height,
+                cell_size: size,
+            });
+        }
+
+        (0..image.height / size)
+            .map(|i| {
+                (0..image.width / size)
+                    .map(|j| {
+                        let (y, x) = (i * size + size / 2, j * size + size / 2);
+                        cell(image.pixels[y * image.width + x], x, y)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn load_bool(&self, reader: impl Read) -> Result<Vec<Vec<bool>>, ImportError> {
+        let image = decode(reader)?;
+        self.cells(&image, |color, _, _| Ok(luminance(color) < self.threshold))
+    }
+
+    // Reads each cell as the state `palette` gives its colour
+    pub fn load_states<T: Copy + PartialEq>(
+        &self,
+        reader: impl Read,
+        palette: &Palette<T>,
+    ) -> Result<Vec<Vec<T>>, ImportError> {
+        let image = decode(reader)?;
+        let distance = |a: Rgb, b: Rgb| {
+            a.iter()
+                .zip(b)
+                .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        };
+
+        self.cells(&image, |color, x, y| {
+            palette
+                .state(color)
+                .or_else(|| {
+                    self.nearest
+                        .then(|| {
+                            palette
+                                .entries()
+                                .iter()
+                                .min_by_key(|&&(_, c)| distance(c, color))
+                                .map(|&(state, _)| state)
+                        })
+                        .flatten()
+                })
+                .ok_or(ImportError::UnknownColor { color, x, y })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::render::{ImageFormat, WorldRenderer};
+
+    fn glider() -> Vec<Vec<bool>> {
+        let mut world = vec![vec![false; 5]; 4];
+        for (i, j) in [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+            world[i][j] = true;
+        }
+        world
+    }
+
+    #[test]
+    fn test_plain_netpbm() {
+        let pbm = b"P1\n# a glider\n5 4\n01000\n0 0 1 0 0\n11100 00000\n";
+        assert_eq!(
+            ImageLoader::new()
+                .load_bool(&pbm[..])
+                .expect("Loading failed"),
+            glider()
+        );
+
+        let pgm = b"P2 4 1 15 0 7 8 15";
+        let loader = ImageLoader::new();
+        assert_eq!(
+            loader.load_bool(&pgm[..]).expect("Loading failed"),
+            vec![vec![true, true, false, false]]
+        );
+        assert_eq!(
+            loader
+                .with_threshold(200)
+                .load_bool(&pgm[..])
+                .expect("Loading failed"),
+            vec![vec![true, true, true, false]]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let renderer = WorldRenderer::new(Palette::monochrome()).with_cell_size(3);
+        let loader = ImageLoader::new().with_cell_size(3);
+
+        for format in [
+            ImageFormat::Pbm,
+            ImageFormat::Pgm,
+            ImageFormat::Ppm,
+            ImageFormat::Png,
+        ] {
+            let mut image = Vec::new();
+            renderer
+                .write_image(&glider(), &mut image, format)
+                .expect("Export failed");
+            assert_eq!(
+                loader.load_bool(image.as_slice()).expect("Loading failed"),
+                glider()
+            );
+        }
+    }
+
+    #[test]
+    fn test_palette() {
+        let palette = Palette::greyscale(3).with_color(1, [200, 0, 0]);
+        let world = vec![vec![0u8, 1, 2], vec![2, 1, 0]];
+
+        let mut image = Vec::new();
+        WorldRenderer::new(palette.clone())
+            .with_cell_size(2)
+            .write_image(&world, &mut image, ImageFormat::Png)
+            .expect("Export failed");
+        assert_eq!(
+            ImageLoader::new()
+                .with_cell_size(2)
+                .load_states(image.as_slice(), &palette)
+                .expect("Loading failed"),
+            world
+        );
+
+        // A slightly off red, as from a hand-drawn image
+        let ppm = b"P3 2 1 255 190 10 5 255 255 255";
+        assert!(matches!(
+            ImageLoader::new().load_states(&ppm[..], &palette),
+            Err(ImportError::UnknownColor {
+                color: [190, 10, 5],
+                x: 0,
+                y: 0
+            })
+        ));
+        assert_eq!(
+            ImageLoader::new()
+                .with_nearest_color(true)
+                .load_states(&ppm[..], &palette)
+                .expect("Loading failed"),
+            vec![vec![1, 0]]
+        );
+    }
+
+    #[test]
+    fn test_png_alpha_and_depth() {
+        let mut image = Vec::new();
+        let mut encoder = png::Encoder::new(&mut image, 3, 1);
+        encoder.set_color(png::ColorType::GrayscaleAlpha);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        let mut writer = encoder.write_header().expect("Export failed");
+        // Opaque black, transparent black and opaque white
+        writer
+            .write_image_data(&[0, 0, 255, 255, 0, 0, 0, 0, 255, 255, 255, 255])
+            .expect("Export failed");
+        writer.finish().expect("Export failed");
+
+        assert_eq!(
+            ImageLoader::new()
+                .load_bool(image.as_slice())
+                .expect("Loading failed"),
+            vec![vec![true, false, false]]
+        );
+    }
+
+    #[test]
+    fn test_sixteen_bit_pgm() {
+        let mut pgm = b"P5 2 1 65535\n".to_vec();
+        pgm.extend([0x10, 0x00, 0xff, 0x00]);
+        assert_eq!(
+            ImageLoader::new()
+                .load_bool(pgm.as_slice())
+                .expect("Loading failed"),
+            vec![vec![true, false]]
+        );
+    }
+
+    #[test]
+    fn test_oversized_header() {
+        // Headers claiming far more pixels than the data holds are caught
+        // before anything that size is allocated
+        let loader = ImageLoader::new();
+        for data in [
+            &b"P5 100000 100000 255\n\x00"[..],
+            b"P6 100000 100000 65535\n\x00\x00",
+            b"P4 100000 100000\n\x00",
+            b"P2 100000 100000 255 0 0",
+            b"P1 100000 100000 0",
+        ] {
+            assert!(matches!(
+                loader.load_bool(data),
+                Err(ImportError::Truncated)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let loader = ImageLoader::new();
+        let load = |data: &[u8]| loader.load_bool(data);
+
+        assert!(matches!(load(b"GIF89a"), Err(ImportError::UnknownFormat)));
+        assert!(matches!(
+            load(b"P7\nWIDTH 1\n"),
+            Err(ImportError::UnsupportedFormat(format)) if format == "PAM"
+        ));
+        assert!(matches!(
+            load(b"P2 3"),
+            Err(ImportError::InvalidHeader(reason)) if reason == "missing or invalid height"
+        ));
+        assert!(matches!(
+            load(b"P5 2 2 0\n"),
+            Err(ImportError::InvalidHeader(_))
+        ));
+        assert!(matches!(load(b"P1 0 3"), Err(ImportError::Empty)));
+        assert!(matches!(
+            load(b"P5 2 2 255\n\x00\x00\x00"),
+            Err(ImportError::Truncated)
+        ));
+        assert!(matches!(
+            load(b"P2 2 2 15 0 0 0 16"),
+            Err(ImportError::InvalidPixel { x: 1, y: 1 })
+        ));
+        assert!(matches!(
+            load(b"P1 2 1 0 x"),
+            Err(ImportError::InvalidPixel { x: 1, y: 0 })
+        ));
+        assert!(matches!(
+            load(b"\x89PNG\r\n\x1a\n"),
+            Err(ImportError::Png(_))
+        ));
+        assert!(matches!(
+            loader.with_cell_size(2).load_bool(&b"P1 3 2 000 000"[..]),
+            Err(ImportError::CellSize {
+                width: 3,
+                height: 2,
+                cell_size: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::automaton::CellularAutomatonWorldSizeError;
+
+use crate::conway::conway_neighbors;
+use crate::dim2::CellularAutomaton2d;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleParseError {
+    // Not of the form `B.../S...` or `MAP...`
+    InvalidFormat,
+    // A letter that does not name a configuration for the count before it
+    InvalidLetter { count: u8, letter: char },
+    // A MAP string that is not 512 bits of base64
+    InvalidMap,
+}
+
+// Neighbours are numbered clockwise from north: N, NE, E, SE, S, SW, W, NW.
+// Each letter names one configuration for its count, up to rotation and
+// reflection; counts above 4 reuse the letters of 8 - count for the dead
+// neighbours.
+const HENSEL_LETTERS: [&[(char, &[usize])]; 5] = [
+    &[],
+    &[('c', &[1]), ('e', &[0])],
+    &[
+        ('c', &[1, 3]),
+        ('e', &[0, 2]),
+        ('k', &[0, 3]),
+        ('a', &[0, 1]),
+        ('i', &[0, 4]),
+        ('n', &[1, 5]),
+    ],
+    &[
+        ('c', &[1, 3, 5]),
+        ('e', &[0, 2, 4]),
+        ('k', &[0, 2, 5]),
+        ('a', &[0, 1, 2]),
+        ('i', &[0, 1, 7]),
+        ('n', &[0, 1, 3]),
+        ('y', &[0, 3, 5]),
+        ('q', &[0, 1, 5]),
+        ('j', &[0, 1, 6]),
+        ('r', &[0, 1, 4]),
+    ],
+    &[
+        ('c', &[1, 3, 5, 7]),
+        ('e', &[0, 2, 4, 6]),
+        ('k', &[0, 1, 3, 6]),
+        ('a', &[0, 1, 2, 3]),
+        ('i', &[0, 1, 3, 4]),
+        ('n', &[0, 1, 3, 7]),
+        ('y', &[0, 1, 3, 5]),
+        ('q', &[0, 1, 2, 5]),
+        ('j', &[0, 1, 4, 6]),
+        ('r', &[0, 1, 2, 4]),
+        ('t', &[0, 1, 4, 7]),
+        ('w', &[0, 1, 5, 6]),
+        ('z', &[0, 1, 4, 5]),
+    ],
+];
+
+// The 3x3 block as a 9-bit index, NW, N, NE, W, centre, E, SW, S, SE from
+// the most significant bit, as used by MAP strings
+fn block_index(block: &[[bool; 3]; 3]) -> usize {
+    block
+        .iter()
+        .flatten()
+        .fold(0, |index, &cell| (index << 1) | cell as usize)
+}
+
+// Ring positions of the index bits, clockwise from north
+const RING_BITS: [usize; 8] = [7, 6, 3, 0, 1, 2, 5, 8];
+
+fn ring_mask(index: usize) -> u8 {
+    RING_BITS.iter().enumerate().fold(0, |mask, (k, &bit)| {
+        mask | ((((index >> bit) & 1) as u8) << k)
+    })
+}
+
+// Every rotation and reflection of a set of ring positions
+fn symmetric_masks(positions: &[usize]) -> Vec<u8> {
+    let mut masks = Vec::new();
+    for rotation in (0..8).step_by(2) {
+        for reflect in [false, true] {
+            let mask = positions.iter().fold(0u8, |mask, &p| {
+                let p = if reflect { (8 - p) % 8 } else { p };
+                mask | (1 << ((p + rotation) % 8))
+            });
+            if !masks.contains(&mask) {
+                masks.push(mask);
+            }
+        }
+    }
+    masks
+}
+
+// Ring masks for a count and letter, or every mask of that count for `None`
+fn configurations(count: u8, letter: Option<char>) -> Option<Vec<u8>> {
+    let masks = match letter {
+        None => (0..=255u8)
+            .filter(|m| m.count_ones() == count as u32)
+            .collect(),
+        Some(letter) => {
+            let (base, complement) = if count > 4 {
+                (8 - count, true)
+            } else {
+                (count, false)
+            };
+            let &(_, positions) = HENSEL_LETTERS[base as usize]
+                .iter()
+                .find(|&&(l, _)| l == letter)?;
+            symmetric_masks(positions)
+                .into_iter()
+                .map(|m| if complement { !m } else { m })
+                .collect()
+        }
+    };
+    Some(masks)
+}
+
+// Parses the part after `B` or `S` into the set of ring masks it allows
+fn parse_conditions(spec: &str) -> Result<[bool; 256], RuleParseError> {
+    let mut allowed = [false; 256];
+    let mut chars = spec.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let count = c
+            .to_digit(10)
+            .filter(|&d| d <= 8)
+            .ok_or(RuleParseError::InvalidFormat)? as u8;
+
+        let negated = chars.next_if_eq(&'-').is_some();
+        let mut letters = Vec::new();
+        while let Some(letter) = chars.next_if(|l| l.is_ascii_lowercase()) {
+            letters.push(letter);
+        }
+        if negated && letters.is_empty() {
+            return Err(RuleParseError::InvalidFormat);
+        }
+
+        let mut masks = Vec::new();
+        for &letter in &letters {
+            masks.extend(
+                configurations(count, Some(letter))
+                    .ok_or(RuleParseError::InvalidLetter { count, letter })?,
+            );
+        }
+
+        let all = configurations(count, None).expect("Counts always have configurations");
+        for mask in all {
+            let listed = masks.contains(&mask);
+            if letters.is_empty() || listed != negated {
+                allowed[mask as usize] = true;
+            }
+        }
+    }
+
+    Ok(allowed)
+}
+
+// A rule on the 3x3 Moore neighbourhood given as its full 512-entry lookup
+// table, indexed as in `block_index`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifeLikeRule {
+    table: [bool; 512],
+}
+
+impl LifeLikeRule {
+    pub fn from_table(table: [bool; 512]) -> Self {
+        Self { table }
+    }
+
+    // Hensel's isotropic non-totalistic notation, e.g. `B2-a/S12` or
+    // `B3/S2-i34q`. Plain totalistic rules such as `B3/S23` are a special
+    // case. Letters are case sensitive, `B` and `S` are not.
+    pub fn from_hensel(rule: &str) -> Result<Self, RuleParseError> {
+        let mut birth = None;
+        let mut survival = None;
+
+        for part in rule.trim().split('/') {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some('B' | 'b') if birth.is_none() => {
+                    birth = Some(parse_conditions(chars.as_str())?)
+                }
+                Some('S' | 's') if survival.is_none() => {
+                    survival = Some(parse_conditions(chars.as_str())?)
+                }
+                _ => return Err(RuleParseError::InvalidFormat),
+            }
+        }
+
+        let (birth, survival) = birth.zip(survival).ok_or(RuleParseError::InvalidFormat)?;
+        let mut table = [false; 512];
+        for (index, next) in table.iter_mut().enumerate() {
+            let alive = (index >> 4) & 1 == 1;
+            let mask = ring_mask(index) as usize;
+            *next = if alive { survival[mask] } else { birth[mask] };
+        }
+
+        Ok(Self { table })
+    }
+
+    // A `MAP` rule: 512 bits in base64, padding optional, with the first
+    // bit for index 0
+    pub fn from_map(rule: &str) -> Result<Self, RuleParseError> {
+        let encoded = rule
+            .trim()
+            .strip_prefix("MAP")
+            .ok_or(RuleParseError::InvalidFormat)?
+            .trim_end_matches('=');
+        if encoded.len() != 86 {
+            return Err(RuleParseError::InvalidMap);
+        }
+
+        let mut bits = Vec::with_capacity(516);
+        for c in encoded.chars() {
+            let value = BASE64
+                .iter()
+                .position(|&b| b as char == c)
+                .ok_or(RuleParseError::InvalidMap)?;
+            bits.extend((0..6).rev().map(|k| (value >> k) & 1 == 1));
+        }
+
+        let mut table = [false; 512];
+        table.copy_from_slice(&bits[..512]);
+        Ok(Self { table })
+    }
+
+    // Either notation
+    pub fn parse(rule: &str) -> Result<Self, RuleParseError> {
+        if rule.trim().starts_with("MAP") {
+            Self::from_map(rule)
+        } else {
+            Self::from_hensel(rule)
+        }
+    }
+
+    // The rule as an unpadded MAP string
+    pub fn to_map(&self) -> String {
+        let mut map = String::from("MAP");
+        for chunk in self.table.chunks(6) {
+            let value = (0..6).fold(0, |value, k| {
+                (value << 1) | chunk.get(k).copied().unwrap_or(false) as usize
+            });
+            map.push(BASE64[value] as char);
+        }
+        map
+    }
+
+    pub fn next_state(&self, block: &[[bool; 3]; 3]) -> bool {
+        self.table[block_index(block)]
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[allow(non_snake_case)]
+pub fn LifeLikeCellularAutomaton(
+    world: Vec<Vec<bool>>,
+    rule: LifeLikeRule,
+    wrapping: bool,
+) -> Result<CellularAutomaton2d<bool, 3, 3>, CellularAutomatonWorldSizeError> {
+    CellularAutomaton2d::<bool, 3, 3>::new(
+        world,
+        move |block| rule.next_state(&block),
+        move |world, i, j| conway_neighbors(world, i, j, wrapping),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::CellularAutomaton;
+    use crate::conway::ConwayCellularAutomaton;
+
+    const LIFE_MAP: &str =
+        "MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA";
+
+    // A dead centre with live cells at the given ring positions
+    fn block(positions: &[usize]) -> [[bool; 3]; 3] {
+        let cells = [
+            (0, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+            (2, 1),
+            (2, 0),
+            (1, 0),
+            (0, 0),
+        ];
+        let mut block = [[false; 3]; 3];
+        for &p in positions {
+            let (i, j) = cells[p];
+            block[i][j] = true;
+        }
+        block
+    }
+
+    #[test]
+    fn test_letters_partition_counts() {
+        for count in 1..8u8 {
+            let base = count.min(8 - count) as usize;
+            let mut seen = Vec::new();
+            for &(letter, _) in HENSEL_LETTERS[base] {
+                seen.extend(configurations(count, Some(letter)).expect("Unknown letter"));
+            }
+            seen.sort();
+            let all = configurations(count, None).expect("Unknown count");
+            assert_eq!(seen, all);
+        }
+    }
+
+    #[test]
+    fn test_life_map() {
+        let life = LifeLikeRule::from_hensel("B3/S23").expect("Parsing failed");
+
+        assert_eq!(life.to_map(), LIFE_MAP);
+        assert_eq!(LifeLikeRule::from_map(LIFE_MAP), Ok(life.clone()));
+        assert_eq!(
+            LifeLikeRule::parse(&format!("{}==", LIFE_MAP)),
+            Ok(life.clone())
+        );
+        assert_eq!(
+            LifeLikeRule::parse("B3S23"),
+            Err(RuleParseError::InvalidFormat)
+        );
+        assert_eq!(LifeLikeRule::parse("s23/b3"), Ok(life));
+    }
+
+    #[test]
+    fn test_matches_conway() {
+        let mut world = vec![vec![false; 12]; 12];
+        for &(i, j) in &[
+            (1, 2),
+            (2, 3),
+            (3, 1),
+            (3, 2),
+            (3, 3),
+            (7, 7),
+            (7, 8),
+            (8, 7),
+            (8, 9),
+        ] {
+            world[i][j] = true;
+        }
+
+        let rule = LifeLikeRule::parse("B3/S23").expect("Parsing failed");
+        let mut life =
+            LifeLikeCellularAutomaton(world.clone(), rule, true).expect("Construction failed");
+        let mut conway = ConwayCellularAutomaton(world, true).expect("Construction failed");
+
+        for _ in 0..30 {
+            life.step();
+            conway.step();
+            assert_eq!(life.world(), conway.world());
+        }
+    }
+
+    #[test]
+    fn test_negated_letters() {
+        let rule = LifeLikeRule::from_hensel("B2-a/S12").expect("Parsing failed");
+
+        // Two adjacent neighbours never give birth, any other pair does
+        assert!(!rule.next_state(&block(&[0, 1])));
+        assert!(!rule.next_state(&block(&[2, 3])));
+        assert!(rule.next_state(&block(&[0, 2])));
+        assert!(rule.next_state(&block(&[1, 5])));
+        assert!(!rule.next_state(&block(&[0, 1, 2])));
+
+        let mut alive = block(&[3]);
+        alive[1][1] = true;
+        assert!(rule.next_state(&alive));
+    }
+
+    #[test]
+    fn test_listed_letters() {
+        let rule = LifeLikeRule::from_hensel("B3/S2-i34q").expect("Parsing failed");
+        let alive = |positions: &[usize]| {
+            let mut b = block(positions);
+            b[1][1] = true;
+            rule.next_state(&b)
+        };
+
+        // 2i is two opposite edges, 4q is an L of three plus the far corner
+        assert!(!alive(&[2, 6]));
+        assert!(alive(&[2, 4]));
+        assert!(alive(&[0, 1, 2, 5]));
+        assert!(alive(&[2, 3, 4, 7]));
+        assert!(!alive(&[0, 1, 2, 3]));
+
+        let five = LifeLikeRule::from_hensel("B5c/S").expect("Parsing failed");
+        // 5c leaves three corners dead, so every edge and one corner is live
+        assert!(five.next_state(&block(&[0, 1, 2, 4, 6])));
+        assert!(!five.next_state(&block(&[0, 1, 2, 3, 4])));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert_eq!(
+            LifeLikeRule::parse("B1k/S"),
+            Err(RuleParseError::InvalidLetter {
+                count: 1,
+                letter: 'k'
+            })
+        );
+        assert_eq!(
+            LifeLikeRule::parse("B4x/S"),
+            Err(RuleParseError::InvalidLetter {
+                count: 4,
+                letter: 'x'
+            })
+        );
+        assert_eq!(
+            LifeLikeRule::parse("B9/S"),
+            Err(RuleParseError::InvalidFormat)
+        );
+        assert_eq!(
+            LifeLikeRule::parse("B3-/S"),
+            Err(RuleParseError::InvalidFormat)
+        );
+        assert_eq!(
+            LifeLikeRule::parse("B3"),
+            Err(RuleParseError::InvalidFormat)
+        );
+        assert_eq!(
+            LifeLikeRule::parse("MAPAAAA"),
+            Err(RuleParseError::InvalidMap)
+        );
+    }
+}
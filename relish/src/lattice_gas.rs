@@ -0,0 +1,470 @@
+use rand::{Rng, SeedableRng};
+
+use crate::automaton::{
+    check_probability, CellularAutomaton, CellularAutomatonWorldSizeError, InvalidProbabilityError,
+};
+
+use crate::stochastic::StochasticRng;
+
+// Each cell holds one bit per lattice direction, set when a particle moves
+// that way. Directions go anticlockwise from east: on the square HPP lattice
+// bits 0..4 are east, north, west and south, and on the hexagonal FHP lattice
+// bits 0..6 are east, north-east, north-west, west, south-west and south-east.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lattice {
+    Hpp,
+    Fhp,
+}
+
+impl Lattice {
+    pub fn directions(&self) -> usize {
+        match self {
+            Lattice::Hpp => 4,
+            Lattice::Fhp => 6,
+        }
+    }
+
+    // Unit velocity of each direction, with y pointing north
+    fn velocity(&self, direction: usize) -> (f64, f64) {
+        let angle = 2.0 * std::f64::consts::PI * direction as f64 / self.directions() as f64;
+        (angle.cos(), angle.sin())
+    }
+
+    // Cell reached from `(i, j)` in `direction`. FHP rows are laid out as a
+    // hexagonal grid with odd rows shifted half a cell to the east.
+    fn neighbor(
+        &self,
+        i: usize,
+        j: usize,
+        direction: usize,
+        height: usize,
+        width: usize,
+    ) -> (usize, usize) {
+        let (di, dj): (isize, isize) = match (self, direction) {
+            (Lattice::Hpp, 0) => (0, 1),
+            (Lattice::Hpp, 1) => (-1, 0),
+            (Lattice::Hpp, 2) => (0, -1),
+            (Lattice::Hpp, _) => (1, 0),
+            (Lattice::Fhp, 0) => (0, 1),
+            (Lattice::Fhp, 3) => (0, -1),
+            (Lattice::Fhp, d) => {
+                let di = if d < 3 { -1 } else { 1 };
+                let east = d == 1 || d == 5;
+                let dj = match (east, i % 2 == 1) {
+                    (true, true) => 1,
+                    (false, false) => -1,
+                    _ => 0,
+                };
+                (di, dj)
+            }
+        };
+
+        (
+            (i as isize + di).rem_euclid(height as isize) as usize,
+            (j as isize + dj).rem_euclid(width as isize) as usize,
+        )
+    }
+
+    // Rotates every particle of the cell half a turn, for bounce-back walls
+    fn reverse(&self, cell: u8) -> u8 {
+        let n = self.directions();
+        let half = n / 2;
+        let mask = (1u8 << n) - 1;
+        ((cell << half) | (cell >> half)) & mask
+    }
+
+    fn collide(&self, cell: u8, chirality: bool) -> u8 {
+        match (self, cell) {
+            // Head-on pairs scatter at right angles
+            (Lattice::Hpp, 0b0101) => 0b1010,
+            (Lattice::Hpp, 0b1010) => 0b0101,
+            // Head-on pairs turn a sixth of a turn either way
+            (Lattice::Fhp, 0b001001) => {
+                if chirality {
+                    0b010010
+                } else {
+                    0b100100
+                }
+            }
+            (Lattice::Fhp, 0b010010) => {
+                if chirality {
+                    0b100100
+                } else {
+                    0b001001
+                }
+            }
+            (Lattice::Fhp, 0b100100) => {
+                if chirality {
+                    0b001001
+                } else {
+                    0b010010
+                }
+            }
+            // Symmetric triples swap onto the other triangle
+            (Lattice::Fhp, 0b010101) => 0b101010,
+            (Lattice::Fhp, 0b101010) => 0b010101,
+            _ => cell,
+        }
+    }
+}
+
+// A lattice gas on a torus. Every step first resolves collisions in place,
+// bouncing particles straight back off obstacle cells, and then moves every
+// particle one cell along its direction. FHP head-on collisions turn
+// anticlockwise on cells where `i + j + generation` is even and clockwise on
+// the others, so runs are deterministic without favouring either side.
+pub struct LatticeGasCellularAutomaton {
+    world: Vec<Vec<u8>>,
+    obstacles: Vec<Vec<bool>>,
+    lattice: Lattice,
+    generation: usize,
+}
+
+impl LatticeGasCellularAutomaton {
+    pub fn new(
+        world: Vec<Vec<u8>>,
+        obstacles: Vec<Vec<bool>>,
+        lattice: Lattice,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        let height = world.len();
+        let width = world.first().map_or(0, |row| row.len());
+        let mask = (1u16 << lattice.directions()) - 1;
+
+        if width == 0
+            || obstacles.len() != height
+            || world.iter().any(|row| row.len() != width)
+            || obstacles.iter().any(|row| row.len() != width)
+            || world.iter().flatten().any(|&cell| cell as u16 & !mask != 0)
+            || (lattice == Lattice::Fhp && height % 2 == 1)
+        {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            obstacles,
+            lattice,
+            generation: 0,
+        })
+    }
+
+    pub fn lattice(&self) -> Lattice {
+        self.lattice
+    }
+
+    pub fn obstacles(&self) -> &Vec<Vec<bool>> {
+        &self.obstacles
+    }
+
+    // Number of particles
+    pub fn mass(&self) -> usize {
+        self.world
+            .iter()
+            .flatten()
+            .map(|cell| cell.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn momentum(&self) -> (f64, f64) {
+        self.world
+            .iter()
+            .flatten()
+            .fold((0.0, 0.0), |total, &cell| {
+                let (x, y) = self.cell_momentum(cell);
+                (total.0 + x, total.1 + y)
+            })
+    }
+
+    fn cell_momentum(&self, cell: u8) -> (f64, f64) {
+        (0..self.lattice.directions())
+            .filter(|&d| (cell >> d) & 1 == 1)
+            .map(|d| self.lattice.velocity(d))
+            .fold((0.0, 0.0), |total, (x, y)| (total.0 + x, total.1 + y))
+    }
+
+    // Mean momentum per fluid cell over `block` x `block` tiles, row by row.
+    // Tiles at the far edges may be smaller, and tiles that are all obstacle
+    // come out as zero.
+    pub fn velocity_field(&self, block: usize) -> Vec<Vec<(f64, f64)>> {
+        let block = block.max(1);
+        let height = self.world.len();
+        let width = self.world[0].len();
+
+        (0..height)
+            .step_by(block)
+            .map(|top| {
+                (0..width)
+                    .step_by(block)
+                    .map(|left| {
+                        let mut total = (0.0, 0.0);
+                        let mut cells = 0;
+                        for i in top..(top + block).min(height) {
+                            for j in left..(left + block).min(width) {
+                                if self.obstacles[i][j] {
+                                    continue;
+                                }
+                                let (x, y) = self.cell_momentum(self.world[i][j]);
+                                total = (total.0 + x, total.1 + y);
+                                cells += 1;
+                            }
+                        }
+                        if cells == 0 {
+                            (0.0, 0.0)
+                        } else {
+                            (total.0 / cells as f64, total.1 / cells as f64)
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl CellularAutomaton for LatticeGasCellularAutomaton {
+    type WorldType = Vec<Vec<u8>>;
+
+    fn step(&mut self) -> usize {
+        let height = self.world.len();
+        let width = self.world[0].len();
+        let lattice = self.lattice;
+
+        for (i, row) in self.world.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = if self.obstacles[i][j] {
+                    lattice.reverse(*cell)
+                } else {
+                    lattice.collide(*cell, (i + j + self.generation) & 1 == 0)
+                };
+            }
+        }
+
+        let mut next = vec![vec![0u8; width]; height];
+        for (i, row) in self.world.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                for d in (0..lattice.directions()).filter(|&d| (cell >> d) & 1 == 1) {
+                    let (n_i, n_j) = lattice.neighbor(i, j, d, height, width);
+                    next[n_i][n_j] |= 1 << d;
+                }
+            }
+        }
+        self.world = next;
+
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len(), self.world[0].len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn HppCellularAutomaton(
+    world: Vec<Vec<u8>>,
+    obstacles: Vec<Vec<bool>>,
+) -> Result<LatticeGasCellularAutomaton, CellularAutomatonWorldSizeError> {
+    LatticeGasCellularAutomaton::new(world, obstacles, Lattice::Hpp)
+}
+
+#[allow(non_snake_case)]
+pub fn FhpCellularAutomaton(
+    world: Vec<Vec<u8>>,
+    obstacles: Vec<Vec<bool>>,
+) -> Result<LatticeGasCellularAutomaton, CellularAutomatonWorldSizeError> {
+    LatticeGasCellularAutomaton::new(world, obstacles, Lattice::Fhp)
+}
+
+// Fills every direction of every cell independently with probability
+// `density`, reproducible from the seed
+pub fn random_gas(
+    height: usize,
+    width: usize,
+    lattice: Lattice,
+    density: f64,
+    seed: u64,
+) -> Result<Vec<Vec<u8>>, InvalidProbabilityError> {
+    let density = check_probability("density", density)?;
+    let mut rng = StochasticRng::seed_from_u64(seed);
+
+    Ok((0..height)
+        .map(|_| {
+            (0..width)
+                .map(|_| {
+                    (0..lattice.directions())
+                        .fold(0u8, |cell, d| cell | ((rng.gen_bool(density) as u8) << d))
+                })
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_obstacles(height: usize, width: usize) -> Vec<Vec<bool>> {
+        vec![vec![false; width]; height]
+    }
+
+    fn assert_close(a: (f64, f64), b: (f64, f64)) {
+        assert!((a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_hpp_free_particle_wraps() {
+        let mut world = vec![vec![0u8; 4]; 3];
+        world[1][3] = 0b0001;
+
+        let mut ca = HppCellularAutomaton(world, no_obstacles(3, 4)).expect("Construction failed");
+        ca.step();
+
+        let mut expected = vec![vec![0u8; 4]; 3];
+        expected[1][0] = 0b0001;
+        assert_eq!(ca.world(), expected);
+    }
+
+    #[test]
+    fn test_hpp_head_on_collision() {
+        let mut world = vec![vec![0u8; 5]; 5];
+        world[2][2] = 0b0101;
+
+        let mut ca = HppCellularAutomaton(world, no_obstacles(5, 5)).expect("Construction failed");
+        ca.step();
+
+        // East and west turn into north and south
+        let mut expected = vec![vec![0u8; 5]; 5];
+        expected[1][2] = 0b0010;
+        expected[3][2] = 0b1000;
+        assert_eq!(ca.world(), expected);
+    }
+
+    #[test]
+    fn test_fhp_neighbors() {
+        // Odd rows sit half a cell east, so the row above row 1 is entered
+        // straight up for north-west and one cell on for north-east
+        let lattice = Lattice::Fhp;
+        assert_eq!(lattice.neighbor(1, 2, 1, 4, 4), (0, 3));
+        assert_eq!(lattice.neighbor(1, 2, 2, 4, 4), (0, 2));
+        assert_eq!(lattice.neighbor(2, 2, 1, 4, 4), (1, 2));
+        assert_eq!(lattice.neighbor(2, 2, 2, 4, 4), (1, 1));
+        assert_eq!(lattice.neighbor(2, 0, 4, 4, 4), (3, 3));
+        assert_eq!(lattice.neighbor(3, 3, 5, 4, 4), (0, 0));
+    }
+
+    #[test]
+    fn test_fhp_collisions() {
+        let lattice = Lattice::Fhp;
+
+        assert_eq!(lattice.collide(0b001001, true), 0b010010);
+        assert_eq!(lattice.collide(0b001001, false), 0b100100);
+        assert_eq!(lattice.collide(0b010101, true), 0b101010);
+        assert_eq!(lattice.collide(0b000011, true), 0b000011);
+        assert_eq!(lattice.reverse(0b000011), 0b011000);
+    }
+
+    #[test]
+    fn test_conservation() {
+        for lattice in [Lattice::Hpp, Lattice::Fhp] {
+            let mut ca = LatticeGasCellularAutomaton::new(
+                random_gas(32, 40, lattice, 0.3, 5).expect("Invalid density"),
+                no_obstacles(32, 40),
+                lattice,
+            )
+            .expect("Construction failed");
+
+            let mass = ca.mass();
+            let momentum = ca.momentum();
+            for _ in 0..100 {
+                ca.step();
+                assert_eq!(ca.mass(), mass);
+                assert_close(ca.momentum(), momentum);
+            }
+        }
+    }
+
+    #[test]
+    fn test_obstacle_bounce_back() {
+        let mut world = vec![vec![0u8; 6]; 1];
+        world[0][1] = 0b0001;
+        let mut obstacles = no_obstacles(1, 6);
+        obstacles[0][3] = true;
+
+        let mut ca = HppCellularAutomaton(world.clone(), obstacles).expect("Construction failed");
+        ca.step();
+        ca.step();
+        assert_eq!(ca.world()[0][3], 0b0001);
+
+        // The wall flips the particle around, then it travels back
+        ca.step();
+        ca.step();
+        let mut expected = vec![vec![0u8; 6]; 1];
+        expected[0][1] = 0b0100;
+        assert_eq!(ca.world(), expected);
+    }
+
+    #[test]
+    fn test_obstacles_conserve_mass() {
+        let mut obstacles = no_obstacles(24, 24);
+        for row in obstacles.iter_mut().take(16).skip(8) {
+            for cell in row.iter_mut().take(14).skip(10) {
+                *cell = true;
+            }
+        }
+
+        let mut ca = FhpCellularAutomaton(
+            random_gas(24, 24, Lattice::Fhp, 0.2, 1).expect("Invalid density"),
+            obstacles,
+        )
+        .expect("Construction failed");
+
+        let mass = ca.mass();
+        for _ in 0..50 {
+            ca.step();
+        }
+        assert_eq!(ca.mass(), mass);
+    }
+
+    #[test]
+    fn test_velocity_field() {
+        let mut ca = HppCellularAutomaton(vec![vec![0b0001; 6]; 4], no_obstacles(4, 6))
+            .expect("Construction failed");
+        ca.step();
+
+        let field = ca.velocity_field(2);
+        assert_eq!((field.len(), field[0].len()), (2, 3));
+        for velocity in field.iter().flatten() {
+            assert_close(*velocity, (1.0, 0.0));
+        }
+
+        let mut ca = FhpCellularAutomaton(vec![vec![0b000010; 4]; 4], no_obstacles(4, 4))
+            .expect("Construction failed");
+        ca.step();
+        assert_close(ca.velocity_field(4)[0][0], (0.5, 3f64.sqrt() / 2.0));
+    }
+
+    #[test]
+    fn test_invalid_lattice_gas() {
+        assert!(HppCellularAutomaton(vec![vec![0b10000; 2]; 2], no_obstacles(2, 2)).is_err());
+        assert!(HppCellularAutomaton(vec![vec![0; 2]; 2], no_obstacles(2, 3)).is_err());
+        assert!(FhpCellularAutomaton(vec![vec![0; 2]; 3], no_obstacles(3, 2)).is_err());
+    }
+
+    #[test]
+    fn test_random_gas_density() {
+        for density in [-0.1, 1.1, f64::NAN] {
+            assert!(random_gas(4, 4, Lattice::Hpp, density, 0).is_err());
+        }
+        assert_eq!(
+            random_gas(3, 3, Lattice::Fhp, 1.0, 0),
+            Ok(vec![vec![0b111111; 3]; 3])
+        );
+    }
+}
@@ -1,5 +1,30 @@
+pub mod apgcode;
 pub mod automaton;
+pub mod census;
+pub mod classify;
+pub mod components;
+pub mod continuous;
 pub mod conway;
+pub mod cycle;
+pub mod cyclic;
 pub mod dim1;
 pub mod dim2;
 pub mod elementary;
+pub mod golly;
+pub mod graph;
+pub mod import;
+pub mod isotropic;
+pub mod lattice_gas;
+pub mod loops;
+pub mod margolus;
+pub mod nonuniform;
+pub mod render;
+pub mod reversible;
+pub mod sandpile;
+pub mod statistics;
+pub mod stochastic;
+pub mod svg;
+pub mod tiling;
+pub mod turmite;
+pub mod update;
+pub mod wireworld;
@@ -0,0 +1,425 @@
+use crate::automaton::CellularAutomatonWorldSizeError;
+
+use crate::dim2::CellularAutomaton2d;
+use crate::golly::{parse_golly_rule, GollyCellularAutomaton, GollyRuleError};
+
+// Langton's loop ships with its rule table and seed below. Other loop rules
+// (Byl, Chou–Reggia, Evoloop) run from their Golly `.rule` files: pass the
+// text to `LoopCellularAutomaton` along with the seed loop.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoopError {
+    Rule(GollyRuleError),
+    // Line and column (1-based) of a character that is not a state
+    Pattern { line: usize, column: usize },
+    WorldSize,
+}
+
+impl From<GollyRuleError> for LoopError {
+    fn from(err: GollyRuleError) -> Self {
+        LoopError::Rule(err)
+    }
+}
+
+impl From<CellularAutomatonWorldSizeError> for LoopError {
+    fn from(_: CellularAutomatonWorldSizeError) -> Self {
+        LoopError::WorldSize
+    }
+}
+
+// Reads a pattern written one digit per cell, with `.` or a space for state 0.
+// Short lines are padded with state 0.
+pub fn parse_state_grid(text: &str) -> Result<Vec<Vec<u8>>, LoopError> {
+    let mut grid = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.chars()
+                .enumerate()
+                .map(|(j, c)| match c {
+                    '.' | ' ' => Ok(0),
+                    _ => c.to_digit(10).map(|d| d as u8).ok_or(LoopError::Pattern {
+                        line: i + 1,
+                        column: j + 1,
+                    }),
+                })
+                .collect::<Result<Vec<u8>, LoopError>>()
+        })
+        .collect::<Result<Vec<Vec<u8>>, LoopError>>()?;
+
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+    for row in grid.iter_mut() {
+        row.resize(width, 0);
+    }
+    Ok(grid)
+}
+
+// Places `pattern` in the middle of an empty `height` x `width` world
+pub fn centered_world(
+    pattern: &[Vec<u8>],
+    height: usize,
+    width: usize,
+) -> Result<Vec<Vec<u8>>, CellularAutomatonWorldSizeError> {
+    let pattern_width = pattern.iter().map(|row| row.len()).max().unwrap_or(0);
+    if pattern.len() > height || pattern_width > width {
+        return Err(CellularAutomatonWorldSizeError);
+    }
+
+    let top = (height - pattern.len()) / 2;
+    let left = (width - pattern_width) / 2;
+    let mut world = vec![vec![0u8; width]; height];
+    for (i, row) in pattern.iter().enumerate() {
+        world[top + i][left..left + row.len()].copy_from_slice(row);
+    }
+    Ok(world)
+}
+
+// Runs the seed loop in `pattern` under a Golly rule, centred in a bounded
+// `height` x `width` world
+#[allow(non_snake_case)]
+pub fn LoopCellularAutomaton(
+    rule: &str,
+    pattern: &str,
+    height: usize,
+    width: usize,
+) -> Result<CellularAutomaton2d<u8, 3, 3>, LoopError> {
+    let rule = parse_golly_rule(rule)?;
+    let world = centered_world(&parse_state_grid(pattern)?, height, width)?;
+
+    Ok(GollyCellularAutomaton(world, rule, false)?)
+}
+
+// Langton's self-reproducing loop (1984), as in Golly: each line is the
+// centre, north, east, south and west states followed by the new state, and
+// cells with no matching transition keep their state
+pub const LANGTONS_LOOPS_RULE: &str = "@RULE LangtonsLoops
+@TABLE
+n_states:8
+neighborhood:vonNeumann
+symmetries:rotate4
+000000
+000012
+000020
+000030
+000050
+000063
+000071
+000112
+000122
+000132
+000212
+000220
+000230
+000262
+000272
+000320
+000525
+000622
+000722
+001022
+001120
+002020
+002030
+002050
+002125
+002220
+002322
+005222
+012321
+012421
+012525
+012621
+012721
+012751
+014221
+014321
+014421
+014721
+016251
+017221
+017255
+017521
+017621
+017721
+025271
+100011
+100061
+100077
+100111
+100121
+100211
+100244
+100277
+100511
+101011
+101111
+101244
+101277
+102026
+102121
+102211
+102244
+102263
+102277
+102327
+102424
+102626
+102644
+102677
+102710
+102727
+105427
+111121
+111221
+111244
+111251
+111261
+111277
+111522
+112121
+112221
+112244
+112251
+112277
+112321
+112424
+112621
+112727
+113221
+122244
+122277
+122434
+122547
+123244
+123277
+124255
+124267
+125275
+200012
+200022
+200042
+200071
+200122
+200152
+200212
+200222
+200232
+200242
+200250
+200262
+200272
+200326
+200423
+200517
+200522
+200575
+200722
+201022
+201122
+201222
+201422
+201722
+202022
+202032
+202052
+202073
+202122
+202152
+202212
+202222
+202272
+202321
+202422
+202452
+202520
+202552
+202622
+202722
+203122
+203216
+203226
+203422
+204222
+205122
+205212
+205222
+205521
+205725
+206222
+206722
+207122
+207222
+207422
+207722
+211222
+211261
+212222
+212242
+212262
+212272
+214222
+215222
+216222
+217222
+222272
+222442
+222462
+222762
+222772
+300013
+300022
+300041
+300076
+300123
+300421
+300622
+301021
+301220
+302511
+401120
+401220
+401250
+402120
+402221
+402326
+402520
+403221
+500022
+500215
+500225
+500232
+500272
+500520
+502022
+502122
+502152
+502220
+502244
+502722
+512122
+512220
+512422
+512722
+600011
+600021
+602120
+612125
+612131
+612225
+700077
+701120
+701220
+701250
+702120
+702221
+702251
+702321
+702525
+702720
+";
+
+// Langton's seed loop. By generation 151 its first daughter has separated
+// and the parent is back in its starting shape.
+pub const LANGTONS_LOOP: &str = " 22222222
+2170140142
+2022222202
+272    212
+212    212
+202    212
+272    212
+21222222122222
+207107107111112
+ 2222222222222";
+
+// Langton's loop centred in a bounded `height` x `width` world
+#[allow(non_snake_case)]
+pub fn LangtonLoopCellularAutomaton(
+    height: usize,
+    width: usize,
+) -> Result<CellularAutomaton2d<u8, 3, 3>, LoopError> {
+    LoopCellularAutomaton(LANGTONS_LOOPS_RULE, LANGTONS_LOOP, height, width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::{CellularAutomaton, NeighborhoodShape};
+    use crate::classify::Pattern;
+    use crate::components::components;
+
+    #[test]
+    fn test_parse_state_grid() {
+        let grid = parse_state_grid("12\n.3 4\n").expect("Parsing failed");
+        assert_eq!(grid, vec![vec![1, 2, 0, 0], vec![0, 3, 0, 4]]);
+
+        assert_eq!(
+            parse_state_grid("11\n1x"),
+            Err(LoopError::Pattern { line: 2, column: 2 })
+        );
+    }
+
+    #[test]
+    fn test_centered_world() {
+        let world = centered_world(&[vec![1, 2]], 3, 4).expect("Construction failed");
+        assert_eq!(world, vec![vec![0; 4], vec![0, 1, 2, 0], vec![0; 4]]);
+
+        assert!(centered_world(&[vec![1; 5]], 3, 4).is_err());
+    }
+
+    #[test]
+    fn test_langton_replicates() {
+        let mut ca = LangtonLoopCellularAutomaton(80, 80).expect("Construction failed");
+        let seed = Pattern::from_world(&ca.world(), false)
+            .expect("Nothing alive")
+            .0;
+
+        for _ in 0..151 {
+            ca.step();
+        }
+
+        // Parent and daughter are apart, and the parent has its shape back
+        let loops = components(&ca.world(), NeighborhoodShape::Moore, 0, false);
+        assert_eq!(loops.len(), 2);
+        assert!(loops.iter().any(|component| {
+            let (pattern, _) =
+                Pattern::from_world(component.world(), false).expect("Nothing alive");
+            pattern == seed
+        }));
+    }
+
+    #[test]
+    fn test_loop_from_rule_text() {
+        // Any Golly table works; here a loop of state 1 cells grows by one
+        // cell of state 2 around it
+        let rule = "@RULE Grow\n@TABLE\nn_states:3\nneighborhood:vonNeumann\nsymmetries:rotate4\nvar a={0,1,2}\nvar b=a\nvar c=a\n0,1,a,b,c,2\n";
+        let mut ca = LoopCellularAutomaton(rule, "11\n11", 4, 4).expect("Construction failed");
+        ca.step();
+
+        assert_eq!(
+            ca.world(),
+            vec![
+                vec![0, 2, 2, 0],
+                vec![2, 1, 1, 2],
+                vec![2, 1, 1, 2],
+                vec![0, 2, 2, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_loops() {
+        assert!(matches!(
+            LoopCellularAutomaton("@RULE Nothing\n", "1", 3, 3),
+            Err(LoopError::Rule(_))
+        ));
+        assert!(matches!(
+            LangtonLoopCellularAutomaton(8, 20),
+            Err(LoopError::WorldSize)
+        ));
+        assert!(matches!(
+            LoopCellularAutomaton(LANGTONS_LOOPS_RULE, "9", 3, 3),
+            Err(LoopError::WorldSize)
+        ));
+    }
+}
@@ -0,0 +1,342 @@
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+// Rules map the 16 possible 2x2 blocks onto new blocks. A block is indexed by
+// its cells as bits: top-left is bit 3, top-right bit 2, bottom-left bit 1 and
+// bottom-right bit 0.
+pub type BlockRule = [u8; 16];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MargolusReverseError {
+    NotInvertible,
+    NoEarlierGeneration,
+}
+
+// A block cellular automaton on the Margolus neighbourhood. The world wraps
+// around and is split into 2x2 blocks, aligned at `(0, 0)` on even
+// generations and at `(1, 1)` on odd ones, so `age() % 2` is the phase used by
+// the next step.
+pub struct MargolusCellularAutomaton {
+    world: Vec<Vec<bool>>,
+    rule: BlockRule,
+    inverse: Option<BlockRule>,
+    generation: usize,
+}
+
+fn invert_rule(rule: &BlockRule) -> Option<BlockRule> {
+    let mut inverse = [0u8; 16];
+    let mut seen = [false; 16];
+
+    for (block, &next) in rule.iter().enumerate() {
+        let next = next as usize;
+        if next >= 16 || seen[next] {
+            return None;
+        }
+        seen[next] = true;
+        inverse[next] = block as u8;
+    }
+
+    Some(inverse)
+}
+
+fn rotate_half_turn(block: u8) -> u8 {
+    (0..4).fold(0, |rotated, bit| {
+        rotated | (((block >> bit) & 1) << (3 - bit))
+    })
+}
+
+impl MargolusCellularAutomaton {
+    pub fn new(
+        world: Vec<Vec<bool>>,
+        rule: BlockRule,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        let height = world.len();
+        let width = world.first().map_or(0, |row| row.len());
+
+        if height == 0
+            || width == 0
+            || height % 2 == 1
+            || width % 2 == 1
+            || world.iter().any(|row| row.len() != width)
+            || rule.iter().any(|&next| next >= 16)
+        {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            rule,
+            inverse: invert_rule(&rule),
+            generation: 0,
+        })
+    }
+
+    pub fn phase(&self) -> usize {
+        self.generation % 2
+    }
+
+    pub fn is_reversible(&self) -> bool {
+        self.inverse.is_some()
+    }
+
+    // Undoes the last step by applying the inverse block rule on the partition
+    // that step used.
+    pub fn step_back(&mut self) -> Result<usize, MargolusReverseError> {
+        let inverse = self.inverse.ok_or(MargolusReverseError::NotInvertible)?;
+        if self.generation == 0 {
+            return Err(MargolusReverseError::NoEarlierGeneration);
+        }
+
+        self.generation -= 1;
+        self.apply(&inverse, self.phase());
+        Ok(self.generation)
+    }
+
+    fn apply(&mut self, rule: &BlockRule, phase: usize) {
+        let height = self.world.len();
+        let width = self.world[0].len();
+
+        for i in (phase..height + phase).step_by(2) {
+            for j in (phase..width + phase).step_by(2) {
+                let cells = [
+                    (i % height, j % width),
+                    (i % height, (j + 1) % width),
+                    ((i + 1) % height, j % width),
+                    ((i + 1) % height, (j + 1) % width),
+                ];
+
+                let block = cells
+                    .iter()
+                    .fold(0u8, |block, &(r, c)| (block << 1) | self.world[r][c] as u8);
+                let next = rule[block as usize];
+
+                for (bit, &(r, c)) in cells.iter().enumerate() {
+                    self.world[r][c] = (next >> (3 - bit)) & 1 == 1;
+                }
+            }
+        }
+    }
+}
+
+impl CellularAutomaton for MargolusCellularAutomaton {
+    type WorldType = Vec<Vec<bool>>;
+
+    fn step(&mut self) -> usize {
+        let rule = self.rule;
+        self.apply(&rule, self.phase());
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len(), self.world[0].len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// Blocks with exactly two live cells are kept, all others are complemented,
+// and blocks that had three live cells are also rotated by a half turn.
+pub fn critters_rule() -> BlockRule {
+    std::array::from_fn(|block| {
+        let block = block as u8;
+        match block.count_ones() {
+            2 => block,
+            3 => rotate_half_turn(!block & 0xf),
+            _ => !block & 0xf,
+        }
+    })
+}
+
+// Fully empty or fully live blocks are complemented, all others are kept.
+pub fn tron_rule() -> BlockRule {
+    std::array::from_fn(|block| match block {
+        0b0000 | 0b1111 => (!block & 0xf) as u8,
+        _ => block as u8,
+    })
+}
+
+// A lone ball crosses its block diagonally, two balls meeting head on along
+// a diagonal bounce off onto the other diagonal, and everything else is
+// left alone so that it can act as a wall.
+pub fn billiard_ball_rule() -> BlockRule {
+    std::array::from_fn(|block| match block as u8 {
+        0b1001 => 0b0110,
+        0b0110 => 0b1001,
+        b if b.count_ones() == 1 => rotate_half_turn(b),
+        b => b,
+    })
+}
+
+#[allow(non_snake_case)]
+pub fn CrittersCellularAutomaton(
+    world: Vec<Vec<bool>>,
+) -> Result<MargolusCellularAutomaton, CellularAutomatonWorldSizeError> {
+    MargolusCellularAutomaton::new(world, critters_rule())
+}
+
+#[allow(non_snake_case)]
+pub fn TronCellularAutomaton(
+    world: Vec<Vec<bool>>,
+) -> Result<MargolusCellularAutomaton, CellularAutomatonWorldSizeError> {
+    MargolusCellularAutomaton::new(world, tron_rule())
+}
+
+#[allow(non_snake_case)]
+pub fn BilliardBallCellularAutomaton(
+    world: Vec<Vec<bool>>,
+) -> Result<MargolusCellularAutomaton, CellularAutomatonWorldSizeError> {
+    MargolusCellularAutomaton::new(world, billiard_ball_rule())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random_world(height: usize, width: usize, mut seed: u32) -> Vec<Vec<bool>> {
+        (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                        (seed >> 16) & 1 == 1
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin_rules_are_reversible() {
+        for rule in [critters_rule(), tron_rule(), billiard_ball_rule()] {
+            assert!(invert_rule(&rule).is_some());
+        }
+    }
+
+    #[test]
+    fn test_phase_follows_age() {
+        let mut ca = TronCellularAutomaton(vec![vec![false; 4]; 4]).expect("Construction failed");
+        assert_eq!(ca.phase(), 0);
+
+        ca.step();
+        assert_eq!((ca.age(), ca.phase()), (1, 1));
+        assert_eq!(ca.world(), vec![vec![true; 4]; 4]);
+
+        ca.step();
+        assert_eq!((ca.age(), ca.phase()), (2, 0));
+        assert_eq!(ca.world(), vec![vec![false; 4]; 4]);
+    }
+
+    #[test]
+    fn test_odd_phase_wraps() {
+        // The odd partition block spanning the seams holds all four corners
+        let mut world = vec![vec![false; 4]; 4];
+        world[0][0] = true;
+        world[0][3] = true;
+        world[3][0] = true;
+        world[3][3] = true;
+
+        let mut ca = TronCellularAutomaton(world).expect("Construction failed");
+        ca.step();
+        ca.step();
+
+        let mut expected = vec![vec![false; 4]; 4];
+        for (i, row) in expected.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = !(i % 3 == 0 && j % 3 == 0);
+            }
+        }
+        assert_eq!(ca.world(), expected);
+    }
+
+    #[test]
+    fn test_billiard_ball_moves_diagonally() {
+        let mut world = vec![vec![false; 8]; 8];
+        world[0][0] = true;
+
+        let mut ca = BilliardBallCellularAutomaton(world).expect("Construction failed");
+
+        for n in 1..6 {
+            ca.step();
+            let mut expected = vec![vec![false; 8]; 8];
+            expected[n][n] = true;
+            assert_eq!(ca.world(), expected);
+        }
+    }
+
+    #[test]
+    fn test_billiard_ball_collision() {
+        // Two balls meet head on in the odd partition block at (3, 3)
+        let mut world = vec![vec![false; 8]; 8];
+        world[2][2] = true;
+        world[5][5] = true;
+
+        let mut ca = BilliardBallCellularAutomaton(world).expect("Construction failed");
+        ca.step();
+        ca.step();
+
+        let mut expected = vec![vec![false; 8]; 8];
+        expected[3][4] = true;
+        expected[4][3] = true;
+        assert_eq!(ca.world(), expected);
+    }
+
+    #[test]
+    fn test_critters_round_trip() {
+        let world = pseudo_random_world(16, 12, 7);
+        let mut ca = CrittersCellularAutomaton(world.clone()).expect("Construction failed");
+
+        for _ in 0..50 {
+            ca.step();
+        }
+        assert_ne!(ca.world(), world);
+
+        for _ in 0..50 {
+            ca.step_back().expect("Reverse step failed");
+        }
+        assert_eq!(ca.world(), world);
+        assert_eq!(ca.age(), 0);
+        assert_eq!(
+            ca.step_back(),
+            Err(MargolusReverseError::NoEarlierGeneration)
+        );
+    }
+
+    #[test]
+    fn test_step_back_mid_run() {
+        let mut ca = BilliardBallCellularAutomaton(pseudo_random_world(10, 10, 3))
+            .expect("Construction failed");
+
+        for _ in 0..7 {
+            ca.step();
+        }
+        let seventh = ca.world();
+        ca.step();
+        ca.step_back().expect("Reverse step failed");
+
+        assert_eq!(ca.world(), seventh);
+        assert_eq!(ca.age(), 7);
+    }
+
+    #[test]
+    fn test_irreversible_rule() {
+        let mut ca = MargolusCellularAutomaton::new(vec![vec![true; 2]; 2], [0; 16])
+            .expect("Construction failed");
+
+        assert!(!ca.is_reversible());
+        ca.step();
+        assert_eq!(ca.step_back(), Err(MargolusReverseError::NotInvertible));
+    }
+
+    #[test]
+    fn test_invalid_margolus_world() {
+        assert!(CrittersCellularAutomaton(vec![vec![false; 4]; 3]).is_err());
+        assert!(CrittersCellularAutomaton(vec![vec![false; 3]; 4]).is_err());
+        assert!(MargolusCellularAutomaton::new(vec![vec![false; 2]; 2], [16; 16]).is_err());
+    }
+}
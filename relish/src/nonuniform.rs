@@ -0,0 +1,242 @@
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+use crate::dim1::{NeighborhoodFn1d, Neighbors1d};
+use crate::elementary::elementary_evolve_builder;
+
+pub type EvolutionFn1d<CellType, const WIDTH: usize> = Box<dyn Fn([CellType; WIDTH]) -> CellType>;
+
+// A 1D automaton where every cell picks its evolution function from a shared
+// rule table through the rule map, so `rule_map()[i]` is the index of the rule
+// applied to cell `i`.
+pub struct NonUniformCellularAutomaton1d<CellType: Clone, const WIDTH: usize> {
+    world: Vec<CellType>,
+    generation: usize,
+    rules: Vec<EvolutionFn1d<CellType, WIDTH>>,
+    rule_map: Vec<usize>,
+    neighborhood_fn: NeighborhoodFn1d<CellType, WIDTH>,
+}
+
+impl<CellType: Clone, const WIDTH: usize> NonUniformCellularAutomaton1d<CellType, WIDTH> {
+    pub fn new(
+        world: Vec<CellType>,
+        rules: Vec<EvolutionFn1d<CellType, WIDTH>>,
+        rule_map: Vec<usize>,
+        neighborhood_fn: impl Fn(&[CellType], usize) -> Neighbors1d<CellType, WIDTH> + 'static,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        if world.len() < WIDTH
+            || rule_map.len() != world.len()
+            || rule_map.iter().any(|&r| r >= rules.len())
+        {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            generation: 0,
+            rules,
+            rule_map,
+            neighborhood_fn: Box::new(neighborhood_fn),
+        })
+    }
+
+    // Appends a rule to the table and returns its index
+    pub fn add_rule(&mut self, rule: impl Fn([CellType; WIDTH]) -> CellType + 'static) -> usize {
+        self.rules.push(Box::new(rule));
+        self.rules.len() - 1
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn rule_map(&self) -> &[usize] {
+        &self.rule_map
+    }
+
+    pub fn set_rule(
+        &mut self,
+        cell: usize,
+        rule: usize,
+    ) -> Result<(), CellularAutomatonWorldSizeError> {
+        if cell >= self.rule_map.len() || rule >= self.rules.len() {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        self.rule_map[cell] = rule;
+        Ok(())
+    }
+
+    pub fn set_rule_map(
+        &mut self,
+        rule_map: Vec<usize>,
+    ) -> Result<(), CellularAutomatonWorldSizeError> {
+        if rule_map.len() != self.world.len() || rule_map.iter().any(|&r| r >= self.rules.len()) {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        self.rule_map = rule_map;
+        Ok(())
+    }
+}
+
+impl<CellType: Clone, const WIDTH: usize> CellularAutomaton
+    for NonUniformCellularAutomaton1d<CellType, WIDTH>
+{
+    type WorldType = Vec<CellType>;
+
+    fn step(&mut self) -> usize {
+        let prev_world = &self.world.clone()[..];
+
+        self.world = (0..prev_world.len())
+            .map(|i| match (self.neighborhood_fn)(prev_world, i) {
+                Neighbors1d::Neighborhood(neighbors) => (self.rules[self.rule_map[i]])(neighbors),
+                Neighbors1d::Edge => prev_world[i].clone(),
+            })
+            .collect();
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// Cells beyond either end are treated as dead, the usual boundary for hybrid
+// automata used as pattern generators
+fn null_boundary_neighbor_fn(world: &[bool], i: usize) -> Neighbors1d<bool, 3> {
+    let left = i > 0 && world[i - 1];
+    let right = world.get(i + 1).copied().unwrap_or(false);
+
+    Neighbors1d::Neighborhood([left, world[i], right])
+}
+
+// A hybrid elementary automaton with null boundaries, where `patterns[i]` is
+// the elementary rule number used by cell `i` (e.g. a mix of 90 and 150)
+#[allow(non_snake_case)]
+pub fn HybridElementaryCellularAutomaton(
+    world: Vec<bool>,
+    patterns: &[u8],
+) -> Result<NonUniformCellularAutomaton1d<bool, 3>, CellularAutomatonWorldSizeError> {
+    let mut table: Vec<u8> = Vec::new();
+    let rule_map = patterns
+        .iter()
+        .map(|&pattern| match table.iter().position(|&p| p == pattern) {
+            Some(idx) => idx,
+            None => {
+                table.push(pattern);
+                table.len() - 1
+            }
+        })
+        .collect();
+
+    let rules = table
+        .into_iter()
+        .map(|pattern| Box::new(elementary_evolve_builder(pattern)) as EvolutionFn1d<bool, 3>)
+        .collect();
+
+    NonUniformCellularAutomaton1d::<bool, 3>::new(world, rules, rule_map, null_boundary_neighbor_fn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn period(ca: &mut NonUniformCellularAutomaton1d<bool, 3>, limit: usize) -> Option<usize> {
+        let start = ca.world();
+        (1..=limit).find(|_| {
+            ca.step();
+            ca.world() == start
+        })
+    }
+
+    #[test]
+    fn test_hybrid_rule_table() {
+        let ca = HybridElementaryCellularAutomaton(vec![false; 5], &[90, 150, 90, 90, 150])
+            .expect("Construction failed");
+
+        assert_eq!(ca.rule_count(), 2);
+        assert_eq!(ca.rule_map(), &[0, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_hybrid_step() {
+        let mut world = vec![false; 6];
+        world[2] = true;
+
+        let mut ca = HybridElementaryCellularAutomaton(world, &[90, 90, 90, 150, 150, 150])
+            .expect("Construction failed");
+        ca.step();
+
+        // Rule 90 drops the centre, rule 150 keeps it
+        assert_eq!(ca.world(), vec![false, true, false, true, false, false]);
+    }
+
+    #[test]
+    fn test_maximal_length_90_150() {
+        // Known maximal length hybrids cycle through every non-zero state
+        let mut seed = vec![false; 4];
+        seed[0] = true;
+        let mut ca = HybridElementaryCellularAutomaton(seed, &[90, 150, 90, 150])
+            .expect("Construction failed");
+        assert_eq!(period(&mut ca, 100), Some(15));
+
+        let mut seed = vec![false; 8];
+        seed[0] = true;
+        let mut ca = HybridElementaryCellularAutomaton(seed, &[90, 90, 90, 90, 90, 150, 150, 90])
+            .expect("Construction failed");
+        assert_eq!(period(&mut ca, 1000), Some(255));
+    }
+
+    #[test]
+    fn test_rule_map_editing() {
+        let mut seed = vec![false; 4];
+        seed[0] = true;
+        let mut ca =
+            HybridElementaryCellularAutomaton(seed, &[90; 4]).expect("Construction failed");
+
+        ca.set_rule_map(vec![0, 0, 0, 0]).expect("Edit failed");
+        let rule_150 = ca.add_rule(elementary_evolve_builder(150));
+        assert_eq!(rule_150, 1);
+
+        ca.set_rule(1, rule_150).expect("Edit failed");
+        ca.set_rule(3, rule_150).expect("Edit failed");
+        assert_eq!(ca.rule_map(), &[0, 1, 0, 1]);
+        assert_eq!(period(&mut ca, 100), Some(15));
+
+        assert!(ca.set_rule(4, 0).is_err());
+        assert!(ca.set_rule(0, 2).is_err());
+        assert!(ca.set_rule_map(vec![0; 3]).is_err());
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules: Vec<EvolutionFn1d<u8, 1>> = vec![Box::new(|x| x[0] + 1), Box::new(|x| x[0] * 2)];
+        let mut ca = NonUniformCellularAutomaton1d::<u8, 1>::new(
+            vec![1, 1, 1],
+            rules,
+            vec![0, 1, 0],
+            |world, i| Neighbors1d::Neighborhood([world[i]]),
+        )
+        .expect("Construction failed");
+
+        ca.step();
+        ca.step();
+
+        assert_eq!(ca.world(), vec![3, 4, 3]);
+    }
+
+    #[test]
+    fn test_invalid_rule_map() {
+        assert!(HybridElementaryCellularAutomaton(vec![false; 4], &[90; 3]).is_err());
+        assert!(HybridElementaryCellularAutomaton(vec![false; 2], &[90; 2]).is_err());
+    }
+}
@@ -0,0 +1,1037 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::automaton::CellularAutomaton;
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Png(png::EncodingError),
+    Gif(gif::EncodingError),
+    // A row of a different width from the one the image was started with
+    RowWidth { expected: usize, found: usize },
+    // More or fewer rows than the image was started with
+    RowCount { expected: usize, found: usize },
+    // More or fewer frames than the animation was started with
+    FrameCount { expected: usize, found: usize },
+    // An image with no pixels, or too large for the format
+    Size,
+    // More colours than a GIF can hold
+    Colors(usize),
+}
+
+impl From<io::Error> for ExportError {
+    fn from(err: io::Error) -> Self {
+        ExportError::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for ExportError {
+    fn from(err: png::EncodingError) -> Self {
+        ExportError::Png(err)
+    }
+}
+
+impl From<gif::EncodingError> for ExportError {
+    fn from(err: gif::EncodingError) -> Self {
+        ExportError::Gif(err)
+    }
+}
+
+// Binary Netpbm bitmaps, greymaps and pixmaps, or RGB PNG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Pbm,
+    Pgm,
+    Ppm,
+    Png,
+}
+
+pub type Rgb = [u8; 3];
+
+// Grey level of a colour, as used for PGM, and for PBM where anything darker
+// than mid-grey is black
+pub(crate) fn luminance([r, g, b]: Rgb) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+}
+
+// Colours for the states of a cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette<T> {
+    colors: Vec<(T, Rgb)>,
+    fallback: Rgb,
+}
+
+impl<T: Copy + PartialEq> Palette<T> {
+    // States without a colour of their own are drawn in `fallback`
+    pub fn new(fallback: Rgb) -> Self {
+        Self {
+            colors: Vec::new(),
+            fallback,
+        }
+    }
+
+    pub fn with_color(mut self, state: T, color: Rgb) -> Self {
+        match self.colors.iter_mut().find(|(s, _)| *s == state) {
+            Some(entry) => entry.1 = color,
+            None => self.colors.push((state, color)),
+        }
+        self
+    }
+
+    pub fn color(&self, state: T) -> Rgb {
+        self.colors
+            .iter()
+            .find(|(s, _)| *s == state)
+            .map_or(self.fallback, |&(_, color)| color)
+    }
+
+    // The first state given `color`, ignoring the fallback
+    pub fn state(&self, color: Rgb) -> Option<T> {
+        self.colors
+            .iter()
+            .find(|&&(_, c)| c == color)
+            .map(|&(state, _)| state)
+    }
+
+    pub(crate) fn entries(&self) -> &[(T, Rgb)] {
+        &self.colors
+    }
+
+    // Every colour the palette can give, each once
+    fn colors(&self) -> Vec<Rgb> {
+        let mut colors = vec![self.fallback];
+        for &(_, color) in &self.colors {
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+        colors
+    }
+}
+
+impl Palette<bool> {
+    // Live cells black on white
+    pub fn monochrome() -> Self {
+        Palette::new([255; 3]).with_color(true, [0; 3])
+    }
+}
+
+impl Palette<u8> {
+    // State 0 white through to the last state black
+    pub fn greyscale(states: u8) -> Self {
+        let last = states.saturating_sub(1).max(1) as u32;
+        (0..states).fold(Palette::new([0; 3]), |palette, state| {
+            let level = (255 - 255 * state as u32 / last) as u8;
+            palette.with_color(state, [level; 3])
+        })
+    }
+}
+
+// What the PNG stream writer writes to. It must own its output for the
+// whole image, so it fills this buffer, which `RasterWriter` empties into
+// the real output after every row.
+#[derive(Clone, Default)]
+struct PngBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl PngBuffer {
+    fn drain_into<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut data = self.0.borrow_mut();
+        out.write_all(&data)?;
+        data.clear();
+        Ok(())
+    }
+}
+
+impl Write for PngBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Encoder<W: Write> {
+    Pnm(W),
+    Png(Box<png::StreamWriter<'static, PngBuffer>>, PngBuffer, W),
+}
+
+// Writes an image one row of pixels at a time, so nothing but the current
+// row is held in memory. The size is fixed up front, as every format here
+// records it in the header.
+pub struct RasterWriter<W: Write> {
+    encoder: Encoder<W>,
+    format: ImageFormat,
+    width: usize,
+    height: usize,
+    rows: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> RasterWriter<W> {
+    pub fn new(
+        mut out: W,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, ExportError> {
+        if width == 0 || height == 0 || width > u32::MAX as usize || height > u32::MAX as usize {
+            return Err(ExportError::Size);
+        }
+
+        let encoder = match format {
+            ImageFormat::Pbm => {
+                write!(out, "P4\n{} {}\n", width, height)?;
+                Encoder::Pnm(out)
+            }
+            ImageFormat::Pgm => {
+                write!(out, "P5\n{} {}\n255\n", width, height)?;
+                Encoder::Pnm(out)
+            }
+            ImageFormat::Ppm => {
+                write!(out, "P6\n{} {}\n255\n", width, height)?;
+                Encoder::Pnm(out)
+            }
+            ImageFormat::Png => {
+                let buffer = PngBuffer::default();
+                let mut encoder = png::Encoder::new(buffer.clone(), width as u32, height as u32);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                let stream = encoder.write_header()?.into_stream_writer()?;
+                buffer.drain_into(&mut out)?;
+                Encoder::Png(Box::new(stream), buffer, out)
+            }
+        };
+
+        Ok(Self {
+            encoder,
+            format,
+            width,
+            height,
+            rows: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn write_row(&mut self, pixels: &[Rgb]) -> Result<(), ExportError> {
+        if pixels.len() != self.width {
+            return Err(ExportError::RowWidth {
+                expected: self.width,
+                found: pixels.len(),
+            });
+        }
+        if self.rows == self.height {
+            return Err(ExportError::RowCount {
+                expected: self.height,
+                found: self.rows + 1,
+            });
+        }
+
+        self.buffer.clear();
+        match self.format {
+            ImageFormat::Pbm => {
+                self.buffer.resize(self.width.div_ceil(8), 0);
+                for (x, &pixel) in pixels.iter().enumerate() {
+                    if luminance(pixel) < 128 {
+                        self.buffer[x / 8] |= 0x80 >> (x % 8);
+                    }
+                }
+            }
+            ImageFormat::Pgm => self.buffer.extend(pixels.iter().map(|&p| luminance(p))),
+            ImageFormat::Ppm | ImageFormat::Png => self.buffer.extend(pixels.iter().flatten()),
+        }
+
+        match &mut self.encoder {
+            Encoder::Pnm(out) => out.write_all(&self.buffer)?,
+            Encoder::Png(stream, buffer, out) => {
+                stream.write_all(&self.buffer)?;
+                buffer.drain_into(out)?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    // Checks every row was written and flushes the image
+    pub fn finish(self) -> Result<(), ExportError> {
+        if self.rows != self.height {
+            return Err(ExportError::RowCount {
+                expected: self.height,
+                found: self.rows,
+            });
+        }
+
+        match self.encoder {
+            Encoder::Pnm(mut out) => out.flush()?,
+            Encoder::Png(stream, buffer, mut out) => {
+                // Finishing drops the PNG writer, which ends the image
+                stream.finish()?;
+                buffer.drain_into(&mut out)?;
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// A space-time diagram of a 1D automaton, one generation per row of cells,
+// written as the generations come so long runs need no history in memory
+pub struct SpaceTimeWriter<W: Write, T> {
+    raster: RasterWriter<W>,
+    palette: Palette<T>,
+    cells: usize,
+    scale: usize,
+}
+
+impl<W: Write, T: Copy + PartialEq> SpaceTimeWriter<W, T> {
+    // Each cell is drawn as a `scale` x `scale` square
+    pub fn new(
+        out: W,
+        format: ImageFormat,
+        cells: usize,
+        generations: usize,
+        scale: usize,
+        palette: Palette<T>,
+    ) -> Result<Self, ExportError> {
+        let width = cells.checked_mul(scale).ok_or(ExportError::Size)?;
+        let height = generations.checked_mul(scale).ok_or(ExportError::Size)?;
+
+        Ok(Self {
+            raster: RasterWriter::new(out, format, width, height)?,
+            palette,
+            cells,
+            scale,
+        })
+    }
+
+    pub fn write_generation(&mut self, world: &[T]) -> Result<(), ExportError> {
+        if world.len() != self.cells {
+            return Err(ExportError::RowWidth {
+                expected: self.cells,
+                found: world.len(),
+            });
+        }
+
+        let mut pixels = Vec::with_capacity(self.cells * self.scale);
+        for &cell in world {
+            let color = self.palette.color(cell);
+            pixels.resize(pixels.len() + self.scale, color);
+        }
+        for _ in 0..self.scale {
+            self.raster.write_row(&pixels)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), ExportError> {
+        self.raster.finish()
+    }
+}
+
+// Draws `generations` rows, starting with the automaton's current world and
+// stepping it between rows
+pub fn write_space_time<C, T, W>(
+    ca: &mut C,
+    generations: usize,
+    out: W,
+    format: ImageFormat,
+    scale: usize,
+    palette: Palette<T>,
+) -> Result<(), ExportError>
+where
+    C: CellularAutomaton<WorldType = Vec<T>>,
+    T: Copy + PartialEq,
+    W: Write,
+{
+    let cells = ca.world().len();
+    let mut writer = SpaceTimeWriter::new(out, format, cells, generations, scale, palette)?;
+
+    for generation in 0..generations {
+        if generation > 0 {
+            ca.step();
+        }
+        writer.write_generation(&ca.world())?;
+    }
+    writer.finish()
+}
+
+// How a 2D world is drawn: each cell as a square of `cell_size` pixels, with
+// optional one pixel grid lines between and around the cells
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldRenderer<T> {
+    palette: Palette<T>,
+    cell_size: usize,
+    grid: Option<Rgb>,
+}
+
+impl<T: Copy + PartialEq> WorldRenderer<T> {
+    pub fn new(palette: Palette<T>) -> Self {
+        Self {
+            palette,
+            cell_size: 1,
+            grid: None,
+        }
+    }
+
+    pub fn with_cell_size(mut self, size: usize) -> Self {
+        self.cell_size = size.max(1);
+        self
+    }
+
+    pub fn with_grid(mut self, color: Rgb) -> Self {
+        self.grid = Some(color);
+        self
+    }
+
+    // The cell under each pixel along one axis, `None` for grid lines
+    fn axis(&self, cells: usize) -> Vec<Option<usize>> {
+        let mut axis = Vec::new();
+        for cell in 0..cells {
+            if self.grid.is_some() {
+                axis.push(None);
+            }
+            axis.resize(axis.len() + self.cell_size, Some(cell));
+        }
+        if self.grid.is_some() {
+            axis.push(None);
+        }
+        axis
+    }
+
+    // Width and height in pixels of a world of `height` x `width` cells
+    pub fn image_size(&self, height: usize, width: usize) -> (usize, usize) {
+        (self.axis(width).len(), self.axis(height).len())
+    }
+
+    fn colors(&self) -> Vec<Rgb> {
+        let mut colors = self.palette.colors();
+        if let Some(grid) = self.grid {
+            if !colors.contains(&grid) {
+                colors.push(grid);
+            }
+        }
+        colors
+    }
+
+    // Calls `f` with each row of pixels of the drawn world in turn
+    fn for_each_row(
+        &self,
+        world: &[Vec<T>],
+        mut f: impl FnMut(&[Rgb]) -> Result<(), ExportError>,
+    ) -> Result<(), ExportError> {
+        let width = world.first().map_or(0, |row| row.len());
+        let columns = self.axis(width);
+        let grid = self.grid.unwrap_or_default();
+
+        let mut pixels = Vec::with_capacity(columns.len());
+        for i in self.axis(world.len()) {
+            pixels.clear();
+            match i {
+                Some(i) => {
+                    if world[i].len() != width {
+                        return Err(ExportError::RowWidth {
+                            expected: width,
+                            found: world[i].len(),
+                        });
+                    }
+                    pixels.extend(
+                        columns
+                            .iter()
+                            .map(|j| j.map_or(grid, |j| self.palette.color(world[i][j]))),
+                    );
+                }
+                None => pixels.resize(columns.len(), grid),
+            }
+            f(&pixels)?;
+        }
+        Ok(())
+    }
+
+    // Draws a single world as an image
+    pub fn write_image<W: Write>(
+        &self,
+        world: &[Vec<T>],
+        out: W,
+        format: ImageFormat,
+    ) -> Result<(), ExportError> {
+        let (width, height) =
+            self.image_size(world.len(), world.first().map_or(0, |row| row.len()));
+        let mut raster = RasterWriter::new(out, format, width, height)?;
+        self.for_each_row(world, |pixels| raster.write_row(pixels))?;
+        raster.finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+enum AnimationEncoder<W: Write> {
+    Gif(gif::Encoder<W>),
+    Apng(png::Writer<W>),
+}
+
+// An animation of a 2D world, one frame at a time, looping forever. GIFs are
+// drawn with the renderer's colours as their palette, so they can have at
+// most 256.
+pub struct AnimationWriter<W: Write, T> {
+    encoder: AnimationEncoder<W>,
+    renderer: WorldRenderer<T>,
+    colors: Vec<Rgb>,
+    world_size: (usize, usize),
+    image_size: (usize, usize),
+    delay: u16,
+    frames: usize,
+    written: usize,
+}
+
+impl<W: Write, T: Copy + PartialEq> AnimationWriter<W, T> {
+    // Frames are of a world of `height` x `width` cells and are shown for
+    // `delay` hundredths of a second each. APNG records the number of
+    // frames up front, so `frames` must be written before finishing.
+    pub fn new(
+        out: W,
+        format: AnimationFormat,
+        renderer: WorldRenderer<T>,
+        height: usize,
+        width: usize,
+        frames: usize,
+        delay: u16,
+    ) -> Result<Self, ExportError> {
+        let image_size = renderer.image_size(height, width);
+        let (image_width, image_height) = image_size;
+        if height == 0 || width == 0 || frames == 0 {
+            return Err(ExportError::Size);
+        }
+
+        let colors = renderer.colors();
+        let encoder = match format {
+            AnimationFormat::Gif => {
+                if image_width > u16::MAX as usize || image_height > u16::MAX as usize {
+                    return Err(ExportError::Size);
+                }
+                if colors.len() > 256 {
+                    return Err(ExportError::Colors(colors.len()));
+                }
+
+                let palette: Vec<u8> = colors.iter().flatten().copied().collect();
+                let mut encoder =
+                    gif::Encoder::new(out, image_width as u16, image_height as u16, &palette)?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                AnimationEncoder::Gif(encoder)
+            }
+            AnimationFormat::Apng => {
+                if image_width > u32::MAX as usize
+                    || image_height > u32::MAX as usize
+                    || frames > u32::MAX as usize
+                {
+                    return Err(ExportError::Size);
+                }
+
+                let mut encoder = png::Encoder::new(out, image_width as u32, image_height as u32);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(frames as u32, 0)?;
+                encoder.set_frame_delay(delay, 100)?;
+                AnimationEncoder::Apng(encoder.write_header()?)
+            }
+        };
+
+        Ok(Self {
+            encoder,
+            renderer,
+            colors,
+            world_size: (height, width),
+            image_size,
+            delay,
+            frames,
+            written: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, world: &[Vec<T>]) -> Result<(), ExportError> {
+        let (height, width) = self.world_size;
+        if world.len() != height {
+            return Err(ExportError::RowCount {
+                expected: height,
+                found: world.len(),
+            });
+        }
+        if let Some(row) = world.iter().find(|row| row.len() != width) {
+            return Err(ExportError::RowWidth {
+                expected: width,
+                found: row.len(),
+            });
+        }
+        if let AnimationEncoder::Apng(_) = self.encoder {
+            if self.written == self.frames {
+                return Err(ExportError::FrameCount {
+                    expected: self.frames,
+                    found: self.written + 1,
+                });
+            }
+        }
+
+        let (image_width, image_height) = self.image_size;
+        let mut buffer = Vec::with_capacity(image_width * image_height * 3);
+        match &mut self.encoder {
+            AnimationEncoder::Gif(encoder) => {
+                let colors = &self.colors;
+                self.renderer.for_each_row(world, |pixels| {
+                    buffer.extend(pixels.iter().map(|pixel| {
+                        colors.iter().position(|color| color == pixel).unwrap_or(0) as u8
+                    }));
+                    Ok(())
+                })?;
+
+                encoder.write_frame(&gif::Frame {
+                    width: image_width as u16,
+                    height: image_height as u16,
+                    delay: self.delay,
+                    buffer: buffer.into(),
+                    ..gif::Frame::default()
+                })?;
+            }
+            AnimationEncoder::Apng(encoder) => {
+                self.renderer.for_each_row(world, |pixels| {
+                    buffer.extend(pixels.iter().flatten());
+                    Ok(())
+                })?;
+                encoder.write_image_data(&buffer)?;
+            }
+        }
+
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), ExportError> {
+        match self.encoder {
+            AnimationEncoder::Gif(encoder) => {
+                encoder.into_inner()?.flush()?;
+            }
+            AnimationEncoder::Apng(encoder) => {
+                if self.written != self.frames {
+                    return Err(ExportError::FrameCount {
+                        expected: self.frames,
+                        found: self.written,
+                    });
+                }
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Steps the automaton through `frames` frames, starting with its current
+// world and skipping `frame_skip` generations between frames, and calls `f`
+// with each frame's world and generation
+fn for_each_frame<C, T>(
+    ca: &mut C,
+    frames: usize,
+    frame_skip: usize,
+    mut f: impl FnMut(&[Vec<T>], usize) -> Result<(), ExportError>,
+) -> Result<(), ExportError>
+where
+    C: CellularAutomaton<WorldType = Vec<Vec<T>>>,
+{
+    for frame in 0..frames {
+        if frame > 0 {
+            for _ in 0..=frame_skip {
+                ca.step();
+            }
+        }
+        f(&ca.world(), ca.age())?;
+    }
+    Ok(())
+}
+
+// Writes each frame as an image of its own, to the output `out` gives for
+// the frame's generation
+pub fn write_frames<C, T, W>(
+    ca: &mut C,
+    frames: usize,
+    frame_skip: usize,
+    mut out: impl FnMut(usize) -> io::Result<W>,
+    format: ImageFormat,
+    renderer: &WorldRenderer<T>,
+) -> Result<(), ExportError>
+where
+    C: CellularAutomaton<WorldType = Vec<Vec<T>>>,
+    T: Copy + PartialEq,
+    W: Write,
+{
+    for_each_frame(ca, frames, frame_skip, |world, generation| {
+        renderer.write_image(world, out(generation)?, format)
+    })
+}
+
+// Writes `frames` frames of the automaton as an animation, starting with its
+// current world and skipping `frame_skip` generations between frames
+pub fn write_animation<C, T, W>(
+    ca: &mut C,
+    frames: usize,
+    frame_skip: usize,
+    out: W,
+    format: AnimationFormat,
+    renderer: WorldRenderer<T>,
+    delay: u16,
+) -> Result<(), ExportError>
+where
+    C: CellularAutomaton<WorldType = Vec<Vec<T>>>,
+    T: Copy + PartialEq,
+    W: Write,
+{
+    let world = ca.world();
+    let (height, width) = (world.len(), world.first().map_or(0, |row| row.len()));
+    let mut writer = AnimationWriter::new(out, format, renderer, height, width, frames, delay)?;
+
+    for_each_frame(ca, frames, frame_skip, |world, _| writer.write_frame(world))?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conway::ConwayCellularAutomaton;
+    use crate::elementary::ElementaryCellularAutomaton;
+
+    fn rule_90(cells: usize) -> crate::dim1::CellularAutomaton1d<bool, 3> {
+        let mut world = vec![false; cells];
+        world[cells / 2] = true;
+        ElementaryCellularAutomaton(world, 90).expect("Construction failed")
+    }
+
+    fn decode_png(data: &[u8]) -> (u32, u32, Vec<u8>) {
+        let decoder = png::Decoder::new(data);
+        let mut reader = decoder.read_info().expect("Invalid PNG");
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).expect("Invalid PNG");
+        pixels.truncate(info.buffer_size());
+        (info.width, info.height, pixels)
+    }
+
+    #[test]
+    fn test_pbm() {
+        let mut out = Vec::new();
+        write_space_time(
+            &mut rule_90(9),
+            3,
+            &mut out,
+            ImageFormat::Pbm,
+            1,
+            Palette::monochrome(),
+        )
+        .expect("Export failed");
+
+        let mut expected = b"P4\n9 3\n".to_vec();
+        expected.extend([0b0000_1000, 0, 0b0001_0100, 0, 0b0010_0010, 0]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_pgm_and_ppm() {
+        let palette = Palette::greyscale(3).with_color(1, [255, 0, 0]);
+        assert_eq!(palette.color(0), [255; 3]);
+        assert_eq!(palette.color(2), [0; 3]);
+        assert_eq!(palette.color(7), [0; 3]);
+
+        let mut out = Vec::new();
+        let mut writer = SpaceTimeWriter::new(&mut out, ImageFormat::Ppm, 3, 1, 1, palette.clone())
+            .expect("Export failed");
+        writer.write_generation(&[0, 1, 2]).expect("Export failed");
+        writer.finish().expect("Export failed");
+
+        let mut expected = b"P6\n3 1\n255\n".to_vec();
+        expected.extend([255, 255, 255, 255, 0, 0, 0, 0, 0]);
+        assert_eq!(out, expected);
+
+        let mut out = Vec::new();
+        let mut writer = SpaceTimeWriter::new(&mut out, ImageFormat::Pgm, 3, 1, 1, palette)
+            .expect("Export failed");
+        writer.write_generation(&[0, 1, 2]).expect("Export failed");
+        writer.finish().expect("Export failed");
+
+        let mut expected = b"P5\n3 1\n255\n".to_vec();
+        expected.extend([255, 76, 0]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_png_scaled() {
+        let palette = Palette::new([0, 0, 0]).with_color(true, [0, 128, 255]);
+        let mut out = Vec::new();
+        write_space_time(&mut rule_90(5), 2, &mut out, ImageFormat::Png, 2, palette)
+            .expect("Export failed");
+
+        let (width, height, pixels) = decode_png(&out);
+        assert_eq!((width, height), (10, 4));
+
+        let pixel = |x: usize, y: usize| &pixels[3 * (y * 10 + x)..3 * (y * 10 + x) + 3];
+        for y in 0..2 {
+            assert_eq!(pixel(4, y), [0, 128, 255]);
+            assert_eq!(pixel(5, y), [0, 128, 255]);
+            assert_eq!(pixel(3, y), [0, 0, 0]);
+        }
+        assert_eq!(pixel(2, 2), [0, 128, 255]);
+        assert_eq!(pixel(4, 3), [0, 0, 0]);
+        assert_eq!(pixel(7, 3), [0, 128, 255]);
+    }
+
+    #[test]
+    fn test_long_run_streams() {
+        // 10k generations stream through one row at a time
+        let mut out = Vec::new();
+        write_space_time(
+            &mut rule_90(64),
+            10_000,
+            &mut out,
+            ImageFormat::Pbm,
+            1,
+            Palette::monochrome(),
+        )
+        .expect("Export failed");
+
+        assert_eq!(out.len(), b"P4\n64 10000\n".len() + 8 * 10_000);
+
+        // Enough data for the PNG to span several IDAT chunks
+        let mut out = Vec::new();
+        write_space_time(
+            &mut rule_90(64),
+            10_000,
+            &mut out,
+            ImageFormat::Png,
+            3,
+            Palette::monochrome(),
+        )
+        .expect("Export failed");
+
+        let (width, height, pixels) = decode_png(&out);
+        assert_eq!((width, height), (192, 30_000));
+        assert_eq!(&pixels[3 * 96..3 * 99], &[0; 9]);
+        assert_eq!(&pixels[3 * 93..3 * 96], &[255; 9]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut out = Vec::new();
+        let mut writer =
+            SpaceTimeWriter::new(&mut out, ImageFormat::Png, 4, 2, 1, Palette::monochrome())
+                .expect("Export failed");
+
+        assert!(matches!(
+            writer.write_generation(&[true; 3]),
+            Err(ExportError::RowWidth {
+                expected: 4,
+                found: 3
+            })
+        ));
+        writer.write_generation(&[true; 4]).expect("Export failed");
+        assert!(matches!(
+            writer.finish(),
+            Err(ExportError::RowCount {
+                expected: 2,
+                found: 1
+            })
+        ));
+
+        assert!(matches!(
+            SpaceTimeWriter::new(Vec::new(), ImageFormat::Pbm, 0, 2, 1, Palette::monochrome()),
+            Err(ExportError::Size)
+        ));
+    }
+
+    fn glider() -> crate::dim2::CellularAutomaton2d<bool, 3, 3> {
+        let mut world = vec![vec![false; 8]; 8];
+        for (i, j) in [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+            world[i][j] = true;
+        }
+        ConwayCellularAutomaton(world, true).expect("Construction failed")
+    }
+
+    #[test]
+    fn test_world_image() {
+        let renderer = WorldRenderer::new(Palette::monochrome())
+            .with_cell_size(2)
+            .with_grid([255, 0, 0]);
+        assert_eq!(renderer.image_size(1, 2), (7, 4));
+
+        let mut out = Vec::new();
+        renderer
+            .write_image(&[vec![true, false]], &mut out, ImageFormat::Ppm)
+            .expect("Export failed");
+
+        let (r, w, b) = ([255, 0, 0], [255; 3], [0; 3]);
+        let grid_row = [r; 7];
+        let cell_row = [r, b, b, r, w, w, r];
+        let mut expected = b"P6\n7 4\n255\n".to_vec();
+        for row in [grid_row, cell_row, cell_row, grid_row] {
+            expected.extend(row.iter().flatten());
+        }
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_frames() {
+        let renderer = WorldRenderer::new(Palette::monochrome()).with_cell_size(3);
+        let path = |generation: usize| {
+            std::env::temp_dir().join(format!(
+                "relish-frame-{}-{}.png",
+                std::process::id(),
+                generation
+            ))
+        };
+        let mut generations = Vec::new();
+        write_frames(
+            &mut glider(),
+            3,
+            3,
+            |generation| {
+                generations.push(generation);
+                std::fs::File::create(path(generation))
+            },
+            ImageFormat::Png,
+            &renderer,
+        )
+        .expect("Export failed");
+        assert_eq!(generations, vec![0, 4, 8]);
+
+        let images: Vec<Vec<u8>> = generations
+            .iter()
+            .map(|&generation| {
+                let image = std::fs::read(path(generation)).expect("Frame not written");
+                std::fs::remove_file(path(generation)).expect("Frame not written");
+                image
+            })
+            .collect();
+
+        // A glider moves one cell down and right every 4 generations
+        let (width, height, first) = decode_png(&images[0]);
+        let (_, _, second) = decode_png(&images[1]);
+        assert_eq!((width, height), (24, 24));
+        let row = 3 * 24;
+        for y in 0..21 {
+            assert_eq!(
+                &first[y * row..y * row + 3 * 21],
+                &second[(y + 3) * row + 9..(y + 4) * row]
+            );
+        }
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_apng() {
+        let renderer = WorldRenderer::new(Palette::monochrome()).with_cell_size(2);
+        let mut out = Vec::new();
+        write_animation(
+            &mut glider(),
+            4,
+            0,
+            &mut out,
+            AnimationFormat::Apng,
+            renderer,
+            10,
+        )
+        .expect("Export failed");
+
+        let decoder = png::Decoder::new(out.as_slice());
+        let mut reader = decoder.read_info().expect("Invalid PNG");
+        let control = reader.info().animation_control.expect("Not animated");
+        assert_eq!((control.num_frames, control.num_plays), (4, 0));
+
+        let mut frames = Vec::new();
+        for _ in 0..4 {
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut pixels).expect("Invalid PNG");
+            let delay = reader.info().frame_control.expect("No frame control");
+            assert_eq!((delay.delay_num, delay.delay_den), (10, 100));
+            frames.push(pixels);
+        }
+        assert_ne!(frames[0], frames[1]);
+    }
+
+    #[test]
+    fn test_gif() {
+        let palette = Palette::new([0, 0, 64]).with_color(true, [255, 255, 0]);
+        let renderer = WorldRenderer::new(palette).with_grid([40, 40, 40]);
+        let mut out = Vec::new();
+        write_animation(
+            &mut glider(),
+            5,
+            1,
+            &mut out,
+            AnimationFormat::Gif,
+            renderer,
+            5,
+        )
+        .expect("Export failed");
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(out.as_slice()).expect("Invalid GIF");
+        assert_eq!((decoder.width(), decoder.height()), (17, 17));
+
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().expect("Invalid GIF") {
+            assert_eq!(frame.delay, 5);
+            frames.push(frame.buffer.to_vec());
+        }
+        assert_eq!(frames.len(), 5);
+
+        // Grid in the corner, the glider's first cell at (0, 1), empty
+        // space at (0, 0)
+        let pixel = |frame: &[u8], x: usize, y: usize| frame[4 * (y * 17 + x)..][..3].to_vec();
+        assert_eq!(pixel(&frames[0], 0, 0), [40, 40, 40]);
+        assert_eq!(pixel(&frames[0], 3, 1), [255, 255, 0]);
+        assert_eq!(pixel(&frames[0], 1, 1), [0, 0, 64]);
+    }
+
+    #[test]
+    fn test_animation_errors() {
+        let palette = (0..=255).fold(Palette::new([0; 3]), |palette, state: u8| {
+            palette.with_color(state, [state, 1, 1])
+        });
+        assert!(matches!(
+            AnimationWriter::new(
+                Vec::new(),
+                AnimationFormat::Gif,
+                WorldRenderer::new(palette),
+                2,
+                2,
+                1,
+                1
+            ),
+            Err(ExportError::Colors(257))
+        ));
+
+        let renderer = WorldRenderer::new(Palette::monochrome());
+        let mut writer =
+            AnimationWriter::new(Vec::new(), AnimationFormat::Apng, renderer, 2, 2, 2, 1)
+                .expect("Export failed");
+        assert!(matches!(
+            writer.write_frame(&[vec![false; 3], vec![false; 3]]),
+            Err(ExportError::RowWidth {
+                expected: 2,
+                found: 3
+            })
+        ));
+        writer
+            .write_frame(&[vec![false; 2], vec![true; 2]])
+            .expect("Export failed");
+        assert!(matches!(
+            writer.finish(),
+            Err(ExportError::FrameCount {
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+}
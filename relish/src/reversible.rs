@@ -0,0 +1,340 @@
+#![allow(unused_imports)]
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+use crate::conway::{conway_evolve, conway_neighbors};
+use crate::dim1::{NeighborhoodFn1d, Neighbors1d};
+use crate::dim2::{NeighborhoodFn2d, Neighbors2d};
+use crate::elementary::{elementary_evolve_builder, elementary_neighbor_fn};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoEarlierGenerationError;
+
+// Second-order automata following Fredkin's construction keep two
+// generations, and the next one is `rule(neighbourhood) XOR previous`. Since
+// the previous generation can be recovered from the next in the same way,
+// any rule becomes reversible. Cells whose neighbourhood is an `Edge` just
+// swap back to their previous value.
+pub struct SecondOrderCellularAutomaton1d<const WIDTH: usize> {
+    previous: Vec<bool>,
+    world: Vec<bool>,
+    generation: usize,
+    evolvution_fn: Box<dyn Fn([bool; WIDTH]) -> bool>,
+    neighborhood_fn: NeighborhoodFn1d<bool, WIDTH>,
+}
+
+impl<const WIDTH: usize> SecondOrderCellularAutomaton1d<WIDTH> {
+    pub fn new(
+        previous: Vec<bool>,
+        world: Vec<bool>,
+        evolvution_fn: impl Fn([bool; WIDTH]) -> bool + 'static,
+        neighborhood_fn: impl Fn(&[bool], usize) -> Neighbors1d<bool, WIDTH> + 'static,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        if world.len() < WIDTH || previous.len() != world.len() {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            previous,
+            world,
+            generation: 0,
+            evolvution_fn: Box::new(evolvution_fn),
+            neighborhood_fn: Box::new(neighborhood_fn),
+        })
+    }
+
+    pub fn previous(&self) -> Vec<bool> {
+        self.previous.clone()
+    }
+
+    // Applies the rule to `source` and XORs in `other`, giving the generation
+    // on the far side of `source` from `other`
+    fn advance(&self, source: &[bool], other: &[bool]) -> Vec<bool> {
+        other
+            .iter()
+            .enumerate()
+            .map(|(i, &o)| match (self.neighborhood_fn)(source, i) {
+                Neighbors1d::Neighborhood(neighbors) => (self.evolvution_fn)(neighbors) ^ o,
+                Neighbors1d::Edge => o,
+            })
+            .collect()
+    }
+
+    pub fn step_back(&mut self) -> Result<usize, NoEarlierGenerationError> {
+        if self.generation == 0 {
+            return Err(NoEarlierGenerationError);
+        }
+
+        let earlier = self.advance(&self.previous, &self.world);
+        self.world = std::mem::replace(&mut self.previous, earlier);
+        self.generation -= 1;
+        Ok(self.generation)
+    }
+}
+
+impl<const WIDTH: usize> CellularAutomaton for SecondOrderCellularAutomaton1d<WIDTH> {
+    type WorldType = Vec<bool>;
+
+    fn step(&mut self) -> usize {
+        let next = self.advance(&self.world, &self.previous);
+        self.previous = std::mem::replace(&mut self.world, next);
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+pub struct SecondOrderCellularAutomaton2d<const HEIGHT: usize, const WIDTH: usize> {
+    previous: Vec<Vec<bool>>,
+    world: Vec<Vec<bool>>,
+    generation: usize,
+    evolvution_fn: Box<dyn Fn([[bool; WIDTH]; HEIGHT]) -> bool>,
+    neighborhood_fn: NeighborhoodFn2d<bool, HEIGHT, WIDTH>,
+}
+
+impl<const HEIGHT: usize, const WIDTH: usize> SecondOrderCellularAutomaton2d<HEIGHT, WIDTH> {
+    pub fn new(
+        previous: Vec<Vec<bool>>,
+        world: Vec<Vec<bool>>,
+        evolvution_fn: impl Fn([[bool; WIDTH]; HEIGHT]) -> bool + 'static,
+        neighborhood_fn: impl Fn(&Vec<Vec<bool>>, usize, usize) -> Neighbors2d<bool, HEIGHT, WIDTH>
+            + 'static,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        let same_shape = previous.len() == world.len()
+            && previous.iter().zip(&world).all(|(p, w)| p.len() == w.len());
+        if world.is_empty() || !same_shape {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            previous,
+            world,
+            generation: 0,
+            evolvution_fn: Box::new(evolvution_fn),
+            neighborhood_fn: Box::new(neighborhood_fn),
+        })
+    }
+
+    pub fn previous(&self) -> Vec<Vec<bool>> {
+        self.previous.clone()
+    }
+
+    fn advance(&self, source: &Vec<Vec<bool>>, other: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        other
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(j, &o)| match (self.neighborhood_fn)(source, i, j) {
+                        Neighbors2d::Neighborhood(neighbors) => (self.evolvution_fn)(neighbors) ^ o,
+                        Neighbors2d::Edge => o,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn step_back(&mut self) -> Result<usize, NoEarlierGenerationError> {
+        if self.generation == 0 {
+            return Err(NoEarlierGenerationError);
+        }
+
+        let earlier = self.advance(&self.previous, &self.world);
+        self.world = std::mem::replace(&mut self.previous, earlier);
+        self.generation -= 1;
+        Ok(self.generation)
+    }
+}
+
+impl<const HEIGHT: usize, const WIDTH: usize> CellularAutomaton
+    for SecondOrderCellularAutomaton2d<HEIGHT, WIDTH>
+{
+    type WorldType = Vec<Vec<bool>>;
+
+    fn step(&mut self) -> usize {
+        let next = self.advance(&self.world, &self.previous);
+        self.previous = std::mem::replace(&mut self.world, next);
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len(), self.world[0].len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// The reversible counterpart of an elementary rule, e.g. Rule 150R for 150
+#[allow(non_snake_case)]
+pub fn ElementaryReversibleCellularAutomaton(
+    previous: Vec<bool>,
+    world: Vec<bool>,
+    pattern: u8,
+) -> Result<SecondOrderCellularAutomaton1d<3>, CellularAutomatonWorldSizeError> {
+    SecondOrderCellularAutomaton1d::<3>::new(
+        previous,
+        world,
+        elementary_evolve_builder(pattern),
+        elementary_neighbor_fn,
+    )
+}
+
+#[allow(non_snake_case)]
+pub fn ConwayReversibleCellularAutomaton(
+    previous: Vec<Vec<bool>>,
+    world: Vec<Vec<bool>>,
+    wrapping: bool,
+) -> Result<SecondOrderCellularAutomaton2d<3, 3>, CellularAutomatonWorldSizeError> {
+    SecondOrderCellularAutomaton2d::<3, 3>::new(
+        previous,
+        world,
+        conway_evolve,
+        move |world, i, j| conway_neighbors(world, i, j, wrapping),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conway::ConwayCellularAutomaton;
+    use crate::elementary::ElementaryCellularAutomaton;
+
+    #[test]
+    fn test_rule_150r_step() {
+        let mut world = vec![false; 9];
+        world[4] = true;
+        let mut previous = vec![false; 9];
+        previous[3] = true;
+
+        let mut ca = ElementaryReversibleCellularAutomaton(previous, world.clone(), 150)
+            .expect("Construction failed");
+        ca.step();
+
+        // Rule 150 spreads the cell to 3, 4 and 5, then the old cell at 3 is
+        // cancelled out
+        let mut expected = vec![false; 9];
+        expected[4] = true;
+        expected[5] = true;
+        assert_eq!(ca.world(), expected);
+        assert_eq!(ca.previous(), world);
+    }
+
+    #[test]
+    fn test_matches_first_order_from_empty_past() {
+        let mut world = vec![false; 31];
+        world[15] = true;
+
+        let mut first =
+            ElementaryCellularAutomaton(world.clone(), 90).expect("Construction failed");
+        let mut second = ElementaryReversibleCellularAutomaton(vec![false; 31], world, 90)
+            .expect("Construction failed");
+
+        first.step();
+        second.step();
+
+        assert_eq!(first.world(), second.world());
+    }
+
+    #[test]
+    fn test_rule_150r_round_trip() {
+        let previous: Vec<bool> = (0..40).map(|i| i % 7 == 0).collect();
+        let world: Vec<bool> = (0..40).map(|i| i % 5 == 1 || i == 20).collect();
+
+        let mut ca = ElementaryReversibleCellularAutomaton(previous.clone(), world.clone(), 150)
+            .expect("Construction failed");
+
+        let mut history = vec![ca.world()];
+        for _ in 0..100 {
+            ca.step();
+            history.push(ca.world());
+        }
+
+        for generation in (0..100).rev() {
+            assert_eq!(ca.step_back(), Ok(generation));
+            assert_eq!(ca.world(), history[generation]);
+        }
+        assert_eq!(ca.previous(), previous);
+        assert_eq!(ca.step_back(), Err(NoEarlierGenerationError));
+    }
+
+    #[test]
+    fn test_conway_reversible_round_trip() {
+        let mut world = vec![vec![false; 12]; 12];
+        world[2][3] = true;
+        world[3][4] = true;
+        world[4][2] = true;
+        world[4][3] = true;
+        world[4][4] = true;
+        let mut previous = vec![vec![false; 12]; 12];
+        previous[8][8] = true;
+        previous[8][9] = true;
+
+        let mut ca = ConwayReversibleCellularAutomaton(previous.clone(), world.clone(), true)
+            .expect("Construction failed");
+
+        for _ in 0..30 {
+            ca.step();
+        }
+        assert_eq!(ca.age(), 30);
+
+        for _ in 0..30 {
+            ca.step_back().expect("Reverse step failed");
+        }
+        assert_eq!(ca.world(), world);
+        assert_eq!(ca.previous(), previous);
+    }
+
+    #[test]
+    fn test_conway_reversible_first_step() {
+        let mut world = vec![vec![false; 5]; 5];
+        world[2][1] = true;
+        world[2][2] = true;
+        world[2][3] = true;
+        let mut previous = vec![vec![false; 5]; 5];
+        previous[1][2] = true;
+
+        let mut life = ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        let mut ca =
+            ConwayReversibleCellularAutomaton(previous, world, false).expect("Construction failed");
+
+        life.step();
+        ca.step();
+
+        // The blinker turns, but its top cell is cancelled by the past
+        let mut expected = life.world();
+        expected[1][2] = false;
+        assert_eq!(ca.world(), expected);
+    }
+
+    #[test]
+    fn test_mismatched_generations() {
+        assert!(
+            ElementaryReversibleCellularAutomaton(vec![false; 4], vec![false; 5], 150).is_err()
+        );
+        assert!(ConwayReversibleCellularAutomaton(
+            vec![vec![false; 3]; 2],
+            vec![vec![false; 3]; 3],
+            true
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+const OFFSETS: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+// An Abelian sandpile on a rectangular grid whose border acts as the sink. A
+// cell holding at least `threshold` grains topples, sending one grain to each
+// of its four neighbours; grains pushed over the edge are lost. `step` does a
+// single synchronous round of toppling, while `stabilize` relaxes the whole
+// pile in one go.
+pub struct Sandpile {
+    world: Vec<Vec<u32>>,
+    threshold: u32,
+    generation: usize,
+    topples: usize,
+    avalanches: Vec<usize>,
+}
+
+impl Sandpile {
+    pub fn new(
+        world: Vec<Vec<u32>>,
+        threshold: u32,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        let width = world.first().map_or(0, |row| row.len());
+        if threshold < 4 || width == 0 || world.iter().any(|row| row.len() != width) {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            threshold,
+            generation: 0,
+            topples: 0,
+            avalanches: Vec::new(),
+        })
+    }
+
+    pub fn empty(
+        height: usize,
+        width: usize,
+        threshold: u32,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        Self::new(vec![vec![0; width]; height], threshold)
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.world.iter().flatten().all(|&g| g < self.threshold)
+    }
+
+    pub fn add_grains(&mut self, row: usize, col: usize, grains: u32) {
+        self.world[row][col] += grains;
+    }
+
+    pub fn add_uniform(&mut self, grains: u32) {
+        self.world.iter_mut().flatten().for_each(|g| *g += grains);
+    }
+
+    // Adds every cell of `other` onto this pile without stabilising
+    pub fn add_world(&mut self, other: &[Vec<u32>]) {
+        for (row, other_row) in self.world.iter_mut().zip(other) {
+            for (g, o) in row.iter_mut().zip(other_row) {
+                *g += o;
+            }
+        }
+    }
+
+    // Drops a single grain and relaxes the pile, returning the avalanche size
+    pub fn drop_grain(&mut self, row: usize, col: usize) -> usize {
+        self.add_grains(row, col, 1);
+        self.stabilize()
+    }
+
+    // Topples until no cell is over the threshold. The number of topples is
+    // returned and recorded as one avalanche.
+    pub fn stabilize(&mut self) -> usize {
+        let height = self.world.len();
+        let width = self.world[0].len();
+        let mut pending: Vec<(usize, usize)> = (0..height)
+            .flat_map(|i| (0..width).map(move |j| (i, j)))
+            .filter(|&(i, j)| self.world[i][j] >= self.threshold)
+            .collect();
+        let mut topples = 0;
+
+        while let Some((i, j)) = pending.pop() {
+            let grains = self.world[i][j];
+            if grains < self.threshold {
+                continue;
+            }
+
+            let count = (grains - self.threshold) / 4 + 1;
+            self.world[i][j] -= 4 * count;
+            topples += count as usize;
+
+            for (n_i, n_j) in neighbors(i, j, height, width) {
+                let before = self.world[n_i][n_j];
+                self.world[n_i][n_j] += count;
+                if before < self.threshold && before + count >= self.threshold {
+                    pending.push((n_i, n_j));
+                }
+            }
+        }
+
+        self.topples += topples;
+        self.avalanches.push(topples);
+        topples
+    }
+
+    pub fn total_topples(&self) -> usize {
+        self.topples
+    }
+
+    pub fn avalanche_sizes(&self) -> &[usize] {
+        &self.avalanches
+    }
+
+    // Number of recorded avalanches of each size
+    pub fn avalanche_histogram(&self) -> BTreeMap<usize, usize> {
+        self.avalanches
+            .iter()
+            .fold(BTreeMap::new(), |mut histogram, &size| {
+                *histogram.entry(size).or_insert(0) += 1;
+                histogram
+            })
+    }
+
+    pub fn grains(&self) -> u64 {
+        self.world.iter().flatten().map(|&g| g as u64).sum()
+    }
+}
+
+fn neighbors(
+    i: usize,
+    j: usize,
+    height: usize,
+    width: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    OFFSETS.iter().filter_map(move |&(d_i, d_j)| {
+        let n_i = i.checked_add_signed(d_i)?;
+        let n_j = j.checked_add_signed(d_j)?;
+        (n_i < height && n_j < width).then_some((n_i, n_j))
+    })
+}
+
+impl CellularAutomaton for Sandpile {
+    type WorldType = Vec<Vec<u32>>;
+
+    fn step(&mut self) -> usize {
+        let height = self.world.len();
+        let width = self.world[0].len();
+        let prev_world = self.world.clone();
+
+        for (i, row) in prev_world.iter().enumerate() {
+            for (j, &grains) in row.iter().enumerate() {
+                if grains >= self.threshold {
+                    self.world[i][j] -= 4;
+                    self.topples += 1;
+                    for (n_i, n_j) in neighbors(i, j, height, width) {
+                        self.world[n_i][n_j] += 1;
+                    }
+                }
+            }
+        }
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len(), self.world[0].len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// The identity element of the sandpile group on a `height` x `width` grid,
+// found as `stab(2m - stab(2m))` where `m` is the maximal stable pile.
+pub fn sandpile_identity(
+    height: usize,
+    width: usize,
+    threshold: u32,
+) -> Result<Vec<Vec<u32>>, CellularAutomatonWorldSizeError> {
+    let max_stable = threshold.saturating_sub(1);
+    let mut pile = Sandpile::new(vec![vec![2 * max_stable; width]; height], threshold)?;
+    pile.stabilize();
+
+    let difference = pile
+        .world
+        .iter()
+        .map(|row| row.iter().map(|&g| 2 * max_stable - g).collect())
+        .collect();
+
+    let mut identity = Sandpile::new(difference, threshold)?;
+    identity.stabilize();
+    Ok(identity.world)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_topple() {
+        let mut pile = Sandpile::empty(3, 3, 4).expect("Construction failed");
+        pile.add_grains(1, 1, 4);
+
+        assert_eq!(pile.stabilize(), 1);
+        assert_eq!(
+            pile.world(),
+            vec![vec![0, 1, 0], vec![1, 0, 1], vec![0, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn test_grains_fall_off_edge() {
+        let mut pile = Sandpile::empty(1, 1, 4).expect("Construction failed");
+        pile.add_grains(0, 0, 9);
+
+        assert_eq!(pile.stabilize(), 2);
+        assert_eq!(pile.world(), vec![vec![1]]);
+        assert_eq!(pile.grains(), 1);
+    }
+
+    #[test]
+    fn test_step_matches_stabilize() {
+        let mut stepped = Sandpile::empty(7, 7, 4).expect("Construction failed");
+        stepped.add_grains(3, 3, 64);
+        let mut relaxed = Sandpile::new(stepped.world(), 4).expect("Construction failed");
+
+        while !stepped.is_stable() {
+            stepped.step();
+        }
+        let topples = relaxed.stabilize();
+
+        assert_eq!(stepped.world(), relaxed.world());
+        assert_eq!(stepped.total_topples(), topples);
+        assert!(stepped.age() > 1);
+    }
+
+    #[test]
+    fn test_symmetric_pile() {
+        let mut pile = Sandpile::empty(21, 21, 4).expect("Construction failed");
+        pile.add_grains(10, 10, 1000);
+        pile.stabilize();
+
+        let world = pile.world();
+        let transposed: Vec<Vec<u32>> = (0..21)
+            .map(|j| world.iter().map(|row| row[j]).collect())
+            .collect();
+        let flipped: Vec<Vec<u32>> = world.iter().rev().cloned().collect();
+
+        assert_eq!(world, transposed);
+        assert_eq!(world, flipped);
+        assert!(pile.is_stable());
+    }
+
+    #[test]
+    fn test_avalanche_statistics() {
+        let mut world = vec![vec![3; 5]; 5];
+        world[0][0] = 0;
+        let mut pile = Sandpile::new(world, 4).expect("Construction failed");
+
+        assert_eq!(pile.drop_grain(0, 0), 0);
+        let big = pile.drop_grain(2, 2);
+
+        assert!(big > 1);
+        assert_eq!(pile.avalanche_sizes(), &[0, big]);
+        assert_eq!(pile.total_topples(), big);
+
+        let histogram = pile.avalanche_histogram();
+        assert_eq!(histogram.get(&0), Some(&1));
+        assert_eq!(histogram.get(&big), Some(&1));
+    }
+
+    #[test]
+    fn test_identity_3x3() {
+        let identity = sandpile_identity(3, 3, 4).expect("Construction failed");
+        assert_eq!(identity, vec![vec![2, 1, 2], vec![1, 0, 1], vec![2, 1, 2]]);
+    }
+
+    #[test]
+    fn test_identity_is_neutral() {
+        let identity = sandpile_identity(12, 17, 4).expect("Construction failed");
+
+        let mut doubled = Sandpile::new(identity.clone(), 4).expect("Construction failed");
+        doubled.add_world(&identity);
+        doubled.stabilize();
+        assert_eq!(doubled.world(), identity);
+
+        // The maximal stable pile is recurrent, so adding the identity keeps it
+        let mut max_pile = Sandpile::new(vec![vec![3; 17]; 12], 4).expect("Construction failed");
+        max_pile.add_world(&identity);
+        max_pile.stabilize();
+        assert_eq!(max_pile.world(), vec![vec![3; 17]; 12]);
+    }
+
+    #[test]
+    fn test_invalid_sandpile() {
+        assert!(Sandpile::empty(3, 3, 3).is_err());
+        assert!(Sandpile::new(vec![vec![0; 3], vec![0; 2]], 4).is_err());
+        assert!(Sandpile::new(vec![], 4).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt::Debug;
+use std::io;
+
+use crate::automaton::CellularAutomaton;
+
+// Worlds whose cells can be visited one by one with their coordinates. The
+// cell type is a parameter so rows and grids of the same cells can both be
+// worlds.
+pub trait CellGrid<T> {
+    // Number of coordinates per cell
+    fn dimensions(&self) -> usize;
+    fn for_each_cell(&self, f: impl FnMut(&[usize], T));
+}
+
+impl<T: Copy> CellGrid<T> for Vec<T> {
+    fn dimensions(&self) -> usize {
+        1
+    }
+
+    fn for_each_cell(&self, mut f: impl FnMut(&[usize], T)) {
+        for (i, &cell) in self.iter().enumerate() {
+            f(&[i], cell);
+        }
+    }
+}
+
+impl<T: Copy> CellGrid<T> for Vec<Vec<T>> {
+    fn dimensions(&self) -> usize {
+        2
+    }
+
+    fn for_each_cell(&self, mut f: impl FnMut(&[usize], T)) {
+        for (i, row) in self.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                f(&[i, j], cell);
+            }
+        }
+    }
+}
+
+// One recorded generation. Cells in the default state are dead, all others
+// alive; births and deaths are counted since the previous sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub generation: usize,
+    pub population: usize,
+    pub density: f64,
+    // Cells in each state, in the order of `Statistics::states`. States first
+    // seen after this sample are left off the end.
+    pub state_counts: Vec<usize>,
+    // Lowest and highest coordinate of a live cell along each dimension,
+    // `None` when nothing is alive
+    pub bounding_box: Option<Vec<(usize, usize)>>,
+    pub births: usize,
+    pub deaths: usize,
+}
+
+// A time series of population statistics
+#[derive(Debug, Clone, Default)]
+pub struct Statistics<T> {
+    samples: Vec<Sample>,
+    states: Vec<T>,
+    previous: Option<Vec<T>>,
+    dimensions: usize,
+}
+
+impl<T: Copy + Eq + Default + Debug> Statistics<T> {
+    pub fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+            states: Vec::new(),
+            previous: None,
+            dimensions: 0,
+        }
+    }
+
+    pub fn record<W: CellGrid<T>>(&mut self, generation: usize, world: &W) {
+        let dead = T::default();
+        let dimensions = world.dimensions();
+
+        let mut cells = Vec::new();
+        let mut state_counts = vec![0; self.states.len()];
+        let mut bounds: Option<Vec<(usize, usize)>> = None;
+        world.for_each_cell(|coordinates, cell| {
+            cells.push(cell);
+
+            let state = match self.states.iter().position(|&s| s == cell) {
+                Some(state) => state,
+                None => {
+                    self.states.push(cell);
+                    state_counts.push(0);
+                    self.states.len() - 1
+                }
+            };
+            state_counts[state] += 1;
+
+            if cell != dead {
+                let bounds =
+                    bounds.get_or_insert_with(|| coordinates.iter().map(|&c| (c, c)).collect());
+                for (bound, &c) in bounds.iter_mut().zip(coordinates) {
+                    *bound = (bound.0.min(c), bound.1.max(c));
+                }
+            }
+        });
+
+        let population = cells.iter().filter(|&&cell| cell != dead).count();
+        let (births, deaths) =
+            match &self.previous {
+                Some(previous) if previous.len() == cells.len() => previous
+                    .iter()
+                    .zip(&cells)
+                    .fold((0, 0), |(births, deaths), (&before, &after)| {
+                        (
+                            births + (before == dead && after != dead) as usize,
+                            deaths + (before != dead && after == dead) as usize,
+                        )
+                    }),
+                _ => (0, 0),
+            };
+
+        self.samples.push(Sample {
+            generation,
+            population,
+            density: population as f64 / cells.len().max(1) as f64,
+            state_counts,
+            bounding_box: bounds,
+            births,
+            deaths,
+        });
+        self.previous = Some(cells);
+        self.dimensions = self.dimensions.max(dimensions);
+    }
+
+    // Records the automaton's current generation
+    pub fn record_automaton<C>(&mut self, ca: &C)
+    where
+        C: CellularAutomaton,
+        C::WorldType: CellGrid<T>,
+    {
+        self.record(ca.age(), &ca.world());
+    }
+
+    // Records the current generation, then `steps` more one after another
+    pub fn run<C>(&mut self, ca: &mut C, steps: usize)
+    where
+        C: CellularAutomaton,
+        C::WorldType: CellGrid<T>,
+    {
+        self.record_automaton(ca);
+        for _ in 0..steps {
+            ca.step();
+            self.record_automaton(ca);
+        }
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    // Every state seen so far, in the order first seen
+    pub fn states(&self) -> &[T] {
+        &self.states
+    }
+
+    // How many cells were in `state` at each sample
+    pub fn state_series(&self, state: T) -> Vec<usize> {
+        let index = self.states.iter().position(|&s| s == state);
+
+        self.samples
+            .iter()
+            .map(|sample| {
+                index
+                    .and_then(|i| sample.state_counts.get(i).copied())
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    pub fn population_series(&self) -> Vec<usize> {
+        self.samples
+            .iter()
+            .map(|sample| sample.population)
+            .collect()
+    }
+
+    // One row per sample: generation, population, density, births, deaths,
+    // the bounding box as `min_` and `max_` columns per dimension (empty
+    // when nothing is alive), then a `count_` column per state
+    pub fn write_csv<W: io::Write>(&self, mut out: W) -> io::Result<()> {
+        let mut header = vec![
+            "generation".to_string(),
+            "population".into(),
+            "density".into(),
+            "births".into(),
+            "deaths".into(),
+        ];
+        for d in 0..self.dimensions {
+            header.push(format!("min_{}", d));
+            header.push(format!("max_{}", d));
+        }
+        for state in &self.states {
+            header.push(csv_field(&format!("count_{:?}", state)));
+        }
+        writeln!(out, "{}", header.join(","))?;
+
+        for sample in &self.samples {
+            let mut row = vec![
+                sample.generation.to_string(),
+                sample.population.to_string(),
+                sample.density.to_string(),
+                sample.births.to_string(),
+                sample.deaths.to_string(),
+            ];
+            for d in 0..self.dimensions {
+                match sample.bounding_box.as_ref().and_then(|b| b.get(d)) {
+                    Some(&(min, max)) => row.extend([min.to_string(), max.to_string()]),
+                    None => row.extend([String::new(), String::new()]),
+                }
+            }
+            for s in 0..self.states.len() {
+                row.push(sample.state_counts.get(s).copied().unwrap_or(0).to_string());
+            }
+            writeln!(out, "{}", row.join(","))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = Vec::new();
+        self.write_csv(&mut csv)
+            .expect("Writing to memory does not fail");
+        String::from_utf8(csv).expect("The CSV is always UTF-8")
+    }
+}
+
+// Quotes a field that would otherwise break the CSV
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conway::ConwayCellularAutomaton;
+    use crate::elementary::ElementaryCellularAutomaton;
+    use crate::wireworld::{parse_wireworld, WireworldCell, WireworldCellularAutomaton};
+
+    #[test]
+    fn test_blinker() {
+        let mut world = vec![vec![false; 5]; 5];
+        world[2][1..4].fill(true);
+        let mut ca = ConwayCellularAutomaton(world, false).expect("Construction failed");
+
+        let mut stats = Statistics::new();
+        stats.run(&mut ca, 2);
+
+        assert_eq!(stats.population_series(), vec![3, 3, 3]);
+        assert_eq!(stats.states(), &[false, true]);
+        assert_eq!(stats.state_series(false), vec![22, 22, 22]);
+
+        let samples = stats.samples();
+        assert_eq!(samples[0].bounding_box, Some(vec![(2, 2), (1, 3)]));
+        assert_eq!(samples[1].bounding_box, Some(vec![(1, 3), (2, 2)]));
+        assert_eq!((samples[0].births, samples[0].deaths), (0, 0));
+        assert_eq!((samples[1].births, samples[1].deaths), (2, 2));
+        assert_eq!(samples[2].generation, 2);
+        assert_eq!(samples[2].density, 3.0 / 25.0);
+    }
+
+    #[test]
+    fn test_one_dimensional() {
+        let mut world = vec![false; 9];
+        world[4] = true;
+        let mut ca = ElementaryCellularAutomaton(world, 90).expect("Construction failed");
+
+        let mut stats = Statistics::new();
+        stats.run(&mut ca, 3);
+
+        assert_eq!(stats.population_series(), vec![1, 2, 2, 4]);
+        assert_eq!(
+            stats
+                .samples()
+                .iter()
+                .map(|s| s.bounding_box.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Some(vec![(4, 4)]),
+                Some(vec![(3, 5)]),
+                Some(vec![(2, 6)]),
+                Some(vec![(1, 7)]),
+            ]
+        );
+        assert_eq!(
+            stats.to_csv().lines().nth(3),
+            Some("2,2,0.2222222222222222,2,2,2,6,7,2")
+        );
+    }
+
+    #[test]
+    fn test_multi_state_csv() {
+        let world = parse_wireworld("tH#.").expect("Parse failed");
+        let mut ca = WireworldCellularAutomaton(world).expect("Construction failed");
+
+        let mut stats = Statistics::new();
+        stats.run(&mut ca, 1);
+
+        assert_eq!(
+            stats.states(),
+            &[
+                WireworldCell::Tail,
+                WireworldCell::Head,
+                WireworldCell::Conductor,
+                WireworldCell::Empty
+            ]
+        );
+        assert_eq!(stats.state_series(WireworldCell::Head), vec![1, 1]);
+        assert_eq!(
+            stats.to_csv(),
+            "generation,population,density,births,deaths,min_0,max_0,min_1,max_1,\
+             count_Tail,count_Head,count_Conductor,count_Empty\n\
+             0,3,0.75,0,0,0,0,0,2,1,1,1,1\n\
+             1,3,0.75,0,0,0,0,0,2,1,1,1,1\n"
+        );
+    }
+
+    #[test]
+    fn test_empty_world() {
+        let mut stats = Statistics::new();
+        stats.record(0, &vec![0u8; 4]);
+        stats.record(1, &vec![0u8, 3, 0, 0]);
+        stats.record(2, &vec![0u8; 4]);
+
+        assert_eq!(stats.samples()[0].bounding_box, None);
+        assert_eq!(stats.samples()[0].state_counts, vec![4]);
+        assert_eq!(stats.samples()[1].births, 1);
+        assert_eq!(stats.samples()[2].deaths, 1);
+        assert_eq!(stats.state_series(3), vec![0, 1, 0]);
+        assert_eq!(stats.state_series(7), vec![0, 0, 0]);
+        assert_eq!(
+            stats.to_csv(),
+            "generation,population,density,births,deaths,min_0,max_0,count_0,count_3\n\
+             0,0,0,0,0,,,4,0\n\
+             1,1,0.25,1,0,1,1,3,1\n\
+             2,0,0,0,1,,,4,0\n"
+        );
+    }
+}
@@ -0,0 +1,486 @@
+#![allow(unused_imports)]
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::automaton::{
+    check_probability, CellularAutomaton, CellularAutomatonConstructionError,
+    CellularAutomatonWorldSizeError,
+};
+
+use crate::dim1::{NeighborhoodFn1d, Neighbors1d};
+use crate::dim2::{NeighborhoodFn2d, Neighbors2d};
+use crate::elementary::{elementary_evolve_builder, elementary_neighbor_fn};
+
+// ChaCha8 gives the same stream for a seed on every platform, so stochastic
+// runs can be repeated exactly.
+pub type StochasticRng = ChaCha8Rng;
+
+type StochasticEvolutionFn<Neighbors, CellType> =
+    Box<dyn Fn(Neighbors, &mut StochasticRng) -> CellType>;
+
+// Like `CellularAutomaton1d`, but the evolution function also draws from a
+// random number generator owned by the automaton. Cells are updated in index
+// order, so a run is fully determined by the seed and the initial world.
+pub struct StochasticCellularAutomaton1d<CellType: Clone, const WIDTH: usize> {
+    world: Vec<CellType>,
+    generation: usize,
+    seed: u64,
+    rng: StochasticRng,
+    evolvution_fn: StochasticEvolutionFn<[CellType; WIDTH], CellType>,
+    neighborhood_fn: NeighborhoodFn1d<CellType, WIDTH>,
+}
+
+impl<CellType: Clone, const WIDTH: usize> StochasticCellularAutomaton1d<CellType, WIDTH> {
+    pub fn new(
+        world: Vec<CellType>,
+        seed: u64,
+        evolvution_fn: impl Fn([CellType; WIDTH], &mut StochasticRng) -> CellType + 'static,
+        neighborhood_fn: impl Fn(&[CellType], usize) -> Neighbors1d<CellType, WIDTH> + 'static,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        if world.len() < WIDTH {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            generation: 0,
+            seed,
+            rng: StochasticRng::seed_from_u64(seed),
+            evolvution_fn: Box::new(evolvution_fn),
+            neighborhood_fn: Box::new(neighborhood_fn),
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Starts over from `world` at generation 0 with the generator reseeded
+    pub fn reload(&mut self, world: Vec<CellType>) -> Result<(), CellularAutomatonWorldSizeError> {
+        if world.len() < WIDTH {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        self.world = world;
+        self.generation = 0;
+        self.rng = StochasticRng::seed_from_u64(self.seed);
+        Ok(())
+    }
+}
+
+impl<CellType: Clone, const WIDTH: usize> CellularAutomaton
+    for StochasticCellularAutomaton1d<CellType, WIDTH>
+{
+    type WorldType = Vec<CellType>;
+
+    fn step(&mut self) -> usize {
+        let prev_world = &self.world.clone()[..];
+
+        self.world = (0..prev_world.len())
+            .map(|i| match (self.neighborhood_fn)(prev_world, i) {
+                Neighbors1d::Neighborhood(neighbors) => {
+                    (self.evolvution_fn)(neighbors, &mut self.rng)
+                }
+                Neighbors1d::Edge => prev_world[i].clone(),
+            })
+            .collect();
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// The 2D counterpart of `StochasticCellularAutomaton1d`, updating cells in
+// row-major order.
+pub struct StochasticCellularAutomaton2d<CellType: Clone, const HEIGHT: usize, const WIDTH: usize> {
+    world: Vec<Vec<CellType>>,
+    generation: usize,
+    seed: u64,
+    rng: StochasticRng,
+    evolvution_fn: StochasticEvolutionFn<[[CellType; WIDTH]; HEIGHT], CellType>,
+    neighborhood_fn: NeighborhoodFn2d<CellType, HEIGHT, WIDTH>,
+}
+
+impl<CellType: Clone, const HEIGHT: usize, const WIDTH: usize>
+    StochasticCellularAutomaton2d<CellType, HEIGHT, WIDTH>
+{
+    pub fn new(
+        world: Vec<Vec<CellType>>,
+        seed: u64,
+        evolvution_fn: impl Fn([[CellType; WIDTH]; HEIGHT], &mut StochasticRng) -> CellType + 'static,
+        neighborhood_fn: impl Fn(&Vec<Vec<CellType>>, usize, usize) -> Neighbors2d<CellType, HEIGHT, WIDTH>
+            + 'static,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        if world.is_empty() {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            generation: 0,
+            seed,
+            rng: StochasticRng::seed_from_u64(seed),
+            evolvution_fn: Box::new(evolvution_fn),
+            neighborhood_fn: Box::new(neighborhood_fn),
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reload(
+        &mut self,
+        world: Vec<Vec<CellType>>,
+    ) -> Result<(), CellularAutomatonWorldSizeError> {
+        if world.is_empty() {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        self.world = world;
+        self.generation = 0;
+        self.rng = StochasticRng::seed_from_u64(self.seed);
+        Ok(())
+    }
+}
+
+impl<CellType: Clone, const HEIGHT: usize, const WIDTH: usize> CellularAutomaton
+    for StochasticCellularAutomaton2d<CellType, HEIGHT, WIDTH>
+{
+    type WorldType = Vec<Vec<CellType>>;
+
+    fn step(&mut self) -> usize {
+        let prev_world = &self.world.clone();
+
+        for (i, row) in self.world.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = match (self.neighborhood_fn)(prev_world, i, j) {
+                    Neighbors2d::Neighborhood(neighbors) => {
+                        (self.evolvution_fn)(neighbors, &mut self.rng)
+                    }
+                    Neighbors2d::Edge => prev_world[i][j].clone(),
+                };
+            }
+        }
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len(), self.world[0].len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// An elementary automaton where every cell is flipped with probability
+// `noise` after the rule is applied
+#[allow(non_snake_case)]
+pub fn NoisyElementaryCellularAutomaton(
+    world: Vec<bool>,
+    pattern: u8,
+    noise: f64,
+    seed: u64,
+) -> Result<StochasticCellularAutomaton1d<bool, 3>, CellularAutomatonConstructionError> {
+    let evolve = elementary_evolve_builder(pattern);
+    let noise = check_probability("noise", noise)?;
+
+    Ok(StochasticCellularAutomaton1d::<bool, 3>::new(
+        world,
+        seed,
+        move |neighbors, rng| evolve(neighbors) ^ rng.gen_bool(noise),
+        elementary_neighbor_fn,
+    )?)
+}
+
+// Domany-Kinzel automaton: a cell becomes active with probability `p1` when
+// exactly one of its two outer neighbours is active, with probability `p2`
+// when both are, and never otherwise.
+#[allow(non_snake_case)]
+pub fn DomanyKinzelCellularAutomaton(
+    world: Vec<bool>,
+    p1: f64,
+    p2: f64,
+    seed: u64,
+) -> Result<StochasticCellularAutomaton1d<bool, 3>, CellularAutomatonConstructionError> {
+    let p1 = check_probability("p1", p1)?;
+    let p2 = check_probability("p2", p2)?;
+
+    Ok(StochasticCellularAutomaton1d::<bool, 3>::new(
+        world,
+        seed,
+        move |neighbors, rng| match (neighbors[0], neighbors[2]) {
+            (false, false) => false,
+            (true, true) => rng.gen_bool(p2),
+            _ => rng.gen_bool(p1),
+        },
+        elementary_neighbor_fn,
+    )?)
+}
+
+// Bond directed percolation, the Domany-Kinzel line `p2 = p(2 - p)`
+#[allow(non_snake_case)]
+pub fn DirectedPercolationCellularAutomaton(
+    world: Vec<bool>,
+    p: f64,
+    seed: u64,
+) -> Result<StochasticCellularAutomaton1d<bool, 3>, CellularAutomatonConstructionError> {
+    let p = check_probability("p", p)?;
+    DomanyKinzelCellularAutomaton(world, p, p * (2.0 - p), seed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ForestCell {
+    #[default]
+    Empty,
+    Tree,
+    Fire,
+}
+
+fn forest_neighbors(
+    world: &[Vec<ForestCell>],
+    i: usize,
+    j: usize,
+    wrapping: bool,
+) -> Neighbors2d<ForestCell, 3, 3> {
+    let height = world.len() as isize;
+    let width = world[0].len() as isize;
+
+    let mut neighbors = [[ForestCell::Empty; 3]; 3];
+
+    for (x, row) in neighbors.iter_mut().enumerate() {
+        for (y, cell) in row.iter_mut().enumerate() {
+            let n_i = i as isize + x as isize - 1;
+            let n_j = j as isize + y as isize - 1;
+
+            *cell = if wrapping {
+                world[((n_i + height) % height) as usize][((n_j + width) % width) as usize]
+            } else if n_i >= 0 && n_i < height && n_j >= 0 && n_j < width {
+                world[n_i as usize][n_j as usize]
+            } else {
+                ForestCell::Empty
+            }
+        }
+    }
+
+    Neighbors2d::Neighborhood(neighbors)
+}
+
+// Drossel-Schwabl forest fire: fires burn out, trees next to a fire (in the
+// von Neumann sense) catch it, other trees are struck by lightning with
+// probability `lightning`, and empty cells grow a tree with probability
+// `growth`.
+#[allow(non_snake_case)]
+pub fn ForestFireCellularAutomaton(
+    world: Vec<Vec<ForestCell>>,
+    growth: f64,
+    lightning: f64,
+    wrapping: bool,
+    seed: u64,
+) -> Result<StochasticCellularAutomaton2d<ForestCell, 3, 3>, CellularAutomatonConstructionError> {
+    let growth = check_probability("growth", growth)?;
+    let lightning = check_probability("lightning", lightning)?;
+
+    Ok(StochasticCellularAutomaton2d::<ForestCell, 3, 3>::new(
+        world,
+        seed,
+        move |n, rng| match n[1][1] {
+            ForestCell::Fire => ForestCell::Empty,
+            ForestCell::Tree => {
+                let burning = [n[0][1], n[1][0], n[1][2], n[2][1]].contains(&ForestCell::Fire);
+                if burning || rng.gen_bool(lightning) {
+                    ForestCell::Fire
+                } else {
+                    ForestCell::Tree
+                }
+            }
+            ForestCell::Empty if rng.gen_bool(growth) => ForestCell::Tree,
+            ForestCell::Empty => ForestCell::Empty,
+        },
+        move |world, i, j| forest_neighbors(world, i, j, wrapping),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::InvalidProbabilityError;
+    use crate::elementary::ElementaryCellularAutomaton;
+
+    fn single_seed(width: usize) -> Vec<bool> {
+        let mut world = vec![false; width];
+        world[width / 2] = true;
+        world
+    }
+
+    fn run<C: CellularAutomaton>(ca: &mut C, steps: usize) -> Vec<C::WorldType> {
+        (0..steps)
+            .map(|_| {
+                ca.step();
+                ca.world()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_run() {
+        let mut a = NoisyElementaryCellularAutomaton(single_seed(64), 110, 0.05, 42)
+            .expect("Construction failed");
+        let mut b = NoisyElementaryCellularAutomaton(single_seed(64), 110, 0.05, 42)
+            .expect("Construction failed");
+        let mut c = NoisyElementaryCellularAutomaton(single_seed(64), 110, 0.05, 43)
+            .expect("Construction failed");
+
+        let run_a = run(&mut a, 50);
+        assert_eq!(run_a, run(&mut b, 50));
+        assert_ne!(run_a, run(&mut c, 50));
+    }
+
+    #[test]
+    fn test_reload_resets_rng() {
+        let mut ca = NoisyElementaryCellularAutomaton(single_seed(64), 30, 0.1, 7)
+            .expect("Construction failed");
+        let first = run(&mut ca, 30);
+
+        ca.reload(single_seed(64)).expect("Reload failed");
+        assert_eq!(ca.age(), 0);
+        assert_eq!(ca.seed(), 7);
+        assert_eq!(run(&mut ca, 30), first);
+
+        assert!(ca.reload(vec![false; 2]).is_err());
+    }
+
+    #[test]
+    fn test_noise_extremes() {
+        let mut quiet = NoisyElementaryCellularAutomaton(single_seed(41), 30, 0.0, 1)
+            .expect("Construction failed");
+        let mut exact =
+            ElementaryCellularAutomaton(single_seed(41), 30).expect("Construction failed");
+        assert_eq!(run(&mut quiet, 20), run(&mut exact, 20));
+
+        let mut flipped = NoisyElementaryCellularAutomaton(vec![false; 8], 0, 1.0, 1)
+            .expect("Construction failed");
+        flipped.step();
+
+        // Edges are left alone, every other cell is flipped
+        let mut expected = vec![true; 8];
+        expected[0] = false;
+        expected[7] = false;
+        assert_eq!(flipped.world(), expected);
+    }
+
+    #[test]
+    fn test_invalid_probabilities() {
+        let rejected = |result: Result<_, CellularAutomatonConstructionError>| match result {
+            Err(CellularAutomatonConstructionError::Probability(InvalidProbabilityError {
+                name,
+                ..
+            })) => Some(name),
+            _ => None,
+        };
+
+        assert_eq!(
+            rejected(NoisyElementaryCellularAutomaton(single_seed(8), 30, 1.5, 1).map(|_| ())),
+            Some("noise")
+        );
+        assert_eq!(
+            rejected(DomanyKinzelCellularAutomaton(single_seed(8), 0.5, f64::NAN, 1).map(|_| ())),
+            Some("p2")
+        );
+        assert_eq!(
+            rejected(DirectedPercolationCellularAutomaton(single_seed(8), -0.1, 1).map(|_| ())),
+            Some("p")
+        );
+        assert_eq!(
+            rejected(
+                ForestFireCellularAutomaton(vec![vec![ForestCell::Empty; 4]; 4], 0.1, 2.0, true, 1)
+                    .map(|_| ())
+            ),
+            Some("lightning")
+        );
+
+        assert!(matches!(
+            NoisyElementaryCellularAutomaton(vec![false; 2], 30, 0.5, 1),
+            Err(CellularAutomatonConstructionError::WorldSize(_))
+        ));
+    }
+
+    #[test]
+    fn test_domany_kinzel_deterministic_limit() {
+        // With p1 = p2 = 1 the rule is the OR of the outer neighbours, rule 250
+        let mut dk = DomanyKinzelCellularAutomaton(single_seed(31), 1.0, 1.0, 5)
+            .expect("Construction failed");
+        let mut exact =
+            ElementaryCellularAutomaton(single_seed(31), 250).expect("Construction failed");
+
+        assert_eq!(run(&mut dk, 10), run(&mut exact, 10));
+    }
+
+    #[test]
+    fn test_directed_percolation_phases() {
+        let alive = |p: f64| {
+            let mut ca = DirectedPercolationCellularAutomaton(vec![true; 200], p, 11)
+                .expect("Construction failed");
+            for _ in 0..300 {
+                ca.step();
+            }
+            ca.world()[1..199].iter().filter(|&&c| c).count()
+        };
+
+        // Bond percolation is critical near p = 0.6447
+        assert_eq!(alive(0.3), 0);
+        assert!(alive(0.9) > 50);
+    }
+
+    #[test]
+    fn test_forest_fire_spreads() {
+        let mut world = vec![vec![ForestCell::Tree; 5]; 5];
+        world[2][2] = ForestCell::Fire;
+
+        let mut ca =
+            ForestFireCellularAutomaton(world, 0.0, 0.0, false, 3).expect("Construction failed");
+        ca.step();
+
+        let world = ca.world();
+        assert_eq!(world[2][2], ForestCell::Empty);
+        assert_eq!(world[1][2], ForestCell::Fire);
+        assert_eq!(world[2][3], ForestCell::Fire);
+        assert_eq!(world[1][1], ForestCell::Tree);
+
+        for _ in 0..10 {
+            ca.step();
+        }
+        assert_eq!(ca.world(), vec![vec![ForestCell::Empty; 5]; 5]);
+    }
+
+    #[test]
+    fn test_forest_fire_reproducible() {
+        let world = vec![vec![ForestCell::Empty; 16]; 16];
+        let mut a = ForestFireCellularAutomaton(world.clone(), 0.05, 0.001, true, 9)
+            .expect("Construction failed");
+        let mut b =
+            ForestFireCellularAutomaton(world, 0.05, 0.001, true, 9).expect("Construction failed");
+
+        let run_a = run(&mut a, 100);
+        assert_eq!(run_a, run(&mut b, 100));
+        assert!(run_a[99].iter().flatten().any(|&c| c == ForestCell::Tree));
+    }
+}
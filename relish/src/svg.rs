@@ -0,0 +1,365 @@
+use std::io::{self, Write};
+
+use crate::automaton::CellularAutomaton;
+use crate::render::{Palette, Rgb};
+
+fn hex([r, g, b]: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// A rectangle of cells in one state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block<T> {
+    state: T,
+    top: usize,
+    left: usize,
+    height: usize,
+    width: usize,
+}
+
+// Covers the cells not in `background` with as few rectangles as runs allow:
+// each row is split into runs of one state, and a run exactly below one in
+// the row above extends that rectangle down
+fn blocks<T: Copy + PartialEq>(rows: &[Vec<T>], background: T) -> Vec<Block<T>> {
+    let mut done = Vec::new();
+    let mut open: Vec<Block<T>> = Vec::new();
+
+    for (i, row) in rows.iter().enumerate() {
+        let mut next = Vec::new();
+        let mut j = 0;
+        while j < row.len() {
+            let state = row[j];
+            let width = row[j..].iter().take_while(|&&cell| cell == state).count();
+            if state != background {
+                match open
+                    .iter()
+                    .position(|b| (b.state, b.left, b.width) == (state, j, width))
+                {
+                    Some(index) => {
+                        let mut block = open.swap_remove(index);
+                        block.height += 1;
+                        next.push(block);
+                    }
+                    None => next.push(Block {
+                        state,
+                        top: i,
+                        left: j,
+                        height: 1,
+                        width,
+                    }),
+                }
+            }
+            j += width;
+        }
+        done.append(&mut open);
+        open = next;
+    }
+    done.append(&mut open);
+
+    done.sort_by_key(|b| (b.top, b.left));
+    done
+}
+
+// Vector drawings of 2D worlds and 1D histories. Cells in the default state
+// are the background; the rest are merged into one path per state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgRenderer<T> {
+    palette: Palette<T>,
+    cell_size: usize,
+    grid: Option<Rgb>,
+    labels: Option<usize>,
+}
+
+impl<T: Copy + PartialEq + Default> SvgRenderer<T> {
+    pub fn new(palette: Palette<T>) -> Self {
+        Self {
+            palette,
+            cell_size: 10,
+            grid: None,
+            labels: None,
+        }
+    }
+
+    // Side of a cell in pixels
+    pub fn with_cell_size(mut self, size: usize) -> Self {
+        self.cell_size = size.max(1);
+        self
+    }
+
+    pub fn with_grid(mut self, color: Rgb) -> Self {
+        self.grid = Some(color);
+        self
+    }
+
+    // Numbers the columns along the top and the rows down the left, from 1
+    // as `print_conway` does, labelling the first cell and every `step`th
+    pub fn with_labels(mut self, step: usize) -> Self {
+        self.labels = Some(step.max(1));
+        self
+    }
+
+    // Draws `rows` with the rows numbered from `first_row` when labelled
+    fn write_rows<W: Write>(
+        &self,
+        rows: &[Vec<T>],
+        first_row: usize,
+        mut out: W,
+    ) -> io::Result<()> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        let size = self.cell_size as f64;
+
+        let label_step = self.labels;
+        let labels = |first: usize, count: usize| -> Vec<(usize, usize)> {
+            match label_step {
+                Some(step) if count > 0 => {
+                    let multiples = ((first / step + 1) * step..first + count).step_by(step);
+                    std::iter::once(first)
+                        .chain(multiples)
+                        .map(|label| (label - first, label))
+                        .collect()
+                }
+                _ => Vec::new(),
+            }
+        };
+        let column_labels = labels(1, width);
+        let row_labels = labels(first_row, height);
+
+        // Room for the labels, at 0.6 em per digit
+        let font = size * 0.8;
+        let (left, top) = match self.labels {
+            Some(_) => {
+                let digits = row_labels
+                    .last()
+                    .map_or(1, |&(_, label)| label.to_string().len());
+                (digits as f64 * font * 0.6 + size * 0.5, font + size * 0.5)
+            }
+            None => (0.0, 0.0),
+        };
+        let (image_width, image_height) = (left + width as f64 * size, top + height as f64 * size);
+
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+             viewBox=\"0 0 {0} {1}\">",
+            image_width, image_height
+        )?;
+
+        // Cells are drawn in cell units, scaled up to pixels
+        writeln!(
+            out,
+            "<g transform=\"translate({} {}) scale({})\" shape-rendering=\"crispEdges\">",
+            left, top, size
+        )?;
+        writeln!(
+            out,
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            width,
+            height,
+            hex(self.palette.color(T::default()))
+        )?;
+
+        let blocks = blocks(rows, T::default());
+        let mut states: Vec<T> = Vec::new();
+        for block in &blocks {
+            if !states.contains(&block.state) {
+                states.push(block.state);
+            }
+        }
+        for state in states {
+            let path: Vec<String> = blocks
+                .iter()
+                .filter(|b| b.state == state)
+                .map(|b| {
+                    format!(
+                        "M{} {}h{}v{}h-{}z",
+                        b.left, b.top, b.width, b.height, b.width
+                    )
+                })
+                .collect();
+            writeln!(
+                out,
+                "<path fill=\"{}\" d=\"{}\"/>",
+                hex(self.palette.color(state)),
+                path.concat()
+            )?;
+        }
+
+        if let Some(grid) = self.grid {
+            let lines: Vec<String> = (0..=height)
+                .map(|i| format!("M0 {}h{}", i, width))
+                .chain((0..=width).map(|j| format!("M{} 0v{}", j, height)))
+                .collect();
+            writeln!(
+                out,
+                "<path fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" d=\"{}\"/>",
+                hex(grid),
+                1.0 / size,
+                lines.concat()
+            )?;
+        }
+        writeln!(out, "</g>")?;
+
+        if self.labels.is_some() {
+            writeln!(
+                out,
+                "<g font-family=\"monospace\" font-size=\"{}\" fill=\"#000000\">",
+                font
+            )?;
+            for (j, label) in column_labels {
+                writeln!(
+                    out,
+                    "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                    left + (j as f64 + 0.5) * size,
+                    font,
+                    label
+                )?;
+            }
+            for (i, label) in row_labels {
+                writeln!(
+                    out,
+                    "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" dominant-baseline=\"central\">{}</text>",
+                    left - size * 0.25,
+                    top + (i as f64 + 0.5) * size,
+                    label
+                )?;
+            }
+            writeln!(out, "</g>")?;
+        }
+
+        writeln!(out, "</svg>")
+    }
+
+    pub fn write_world<W: Write>(&self, world: &[Vec<T>], out: W) -> io::Result<()> {
+        self.write_rows(world, 1, out)
+    }
+
+    // A space-time diagram, one row per generation, with the rows labelled
+    // by generation from `first_generation`
+    pub fn write_history<W: Write>(
+        &self,
+        history: &[Vec<T>],
+        first_generation: usize,
+        out: W,
+    ) -> io::Result<()> {
+        self.write_rows(history, first_generation, out)
+    }
+
+    // Draws `generations` rows, starting with the automaton's current world
+    // and stepping it between rows
+    pub fn write_space_time<C, W>(&self, ca: &mut C, generations: usize, out: W) -> io::Result<()>
+    where
+        C: CellularAutomaton<WorldType = Vec<T>>,
+        W: Write,
+    {
+        let first_generation = ca.age();
+        let mut history = Vec::with_capacity(generations);
+        for generation in 0..generations {
+            if generation > 0 {
+                ca.step();
+            }
+            history.push(ca.world());
+        }
+        self.write_history(&history, first_generation, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::elementary::ElementaryCellularAutomaton;
+
+    fn render<T: Copy + PartialEq + Default>(
+        renderer: &SvgRenderer<T>,
+        world: &[Vec<T>],
+    ) -> String {
+        let mut out = Vec::new();
+        renderer
+            .write_world(world, &mut out)
+            .expect("Export failed");
+        String::from_utf8(out).expect("SVG is UTF-8")
+    }
+
+    #[test]
+    fn test_blocks() {
+        let world = vec![
+            vec![false, true, true, false],
+            vec![false, true, true, false],
+            vec![true, true, true, true],
+        ];
+        let found = blocks(&world, false);
+
+        assert_eq!(
+            found
+                .iter()
+                .map(|b| (b.top, b.left, b.height, b.width))
+                .collect::<Vec<_>>(),
+            vec![(0, 1, 2, 2), (2, 0, 1, 4)]
+        );
+
+        // A filled world is one rectangle however large
+        assert_eq!(blocks(&vec![vec![true; 50]; 40], false).len(), 1);
+    }
+
+    #[test]
+    fn test_world() {
+        let world = vec![vec![false, true, true], vec![false, true, true]];
+        let renderer = SvgRenderer::new(Palette::monochrome()).with_cell_size(4);
+
+        assert_eq!(
+            render(&renderer, &world),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"12\" height=\"8\" \
+             viewBox=\"0 0 12 8\">\n\
+             <g transform=\"translate(0 0) scale(4)\" shape-rendering=\"crispEdges\">\n\
+             <rect width=\"3\" height=\"2\" fill=\"#ffffff\"/>\n\
+             <path fill=\"#000000\" d=\"M1 0h2v2h-2z\"/>\n\
+             </g>\n\
+             </svg>\n"
+        );
+    }
+
+    #[test]
+    fn test_states_grid_and_labels() {
+        let palette = Palette::new([0, 0, 0])
+            .with_color(1, [255, 0, 0])
+            .with_color(2, [0, 0, 255]);
+        let world: Vec<Vec<u8>> = vec![vec![1, 2, 0], vec![0, 2, 1]];
+        let svg = render(
+            &SvgRenderer::new(palette)
+                .with_grid([200, 200, 200])
+                .with_labels(1),
+            &world,
+        );
+
+        assert!(svg.contains("<path fill=\"#ff0000\" d=\"M0 0h1v1h-1zM2 1h1v1h-1z\"/>"));
+        assert!(svg.contains("<path fill=\"#0000ff\" d=\"M1 0h1v2h-1z\"/>"));
+        assert!(svg.contains("stroke=\"#c8c8c8\" stroke-width=\"0.1\" d=\"M0 0h3M0 1h3M0 2h3"));
+        assert_eq!(svg.matches("<text").count(), 5);
+        assert!(svg.contains("text-anchor=\"middle\">3</text>"));
+        assert!(svg.contains("text-anchor=\"end\" dominant-baseline=\"central\">2</text>"));
+    }
+
+    #[test]
+    fn test_space_time() {
+        let mut world = vec![false; 9];
+        world[4] = true;
+        let mut ca = ElementaryCellularAutomaton(world, 90).expect("Construction failed");
+        ca.step();
+
+        let mut out = Vec::new();
+        SvgRenderer::new(Palette::monochrome())
+            .with_labels(2)
+            .write_space_time(&mut ca, 12, &mut out)
+            .expect("Export failed");
+        let svg = String::from_utf8(out).expect("SVG is UTF-8");
+
+        assert_eq!(ca.age(), 12);
+        // Rows are labelled by generation, the first and every second one
+        for label in ["1", "2", "4", "12"] {
+            assert!(svg.contains(&format!("dominant-baseline=\"central\">{}</text>", label)));
+        }
+        assert!(!svg.contains("dominant-baseline=\"central\">3</text>"));
+        assert!(svg.contains("d=\"M3 0h1v1h-1zM5 0h1v1h-1z"));
+    }
+}
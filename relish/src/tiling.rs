@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use crate::automaton::CellularAutomatonWorldSizeError;
+
+// Adjacency lists ready for `GraphCellularAutomaton`, along with the centre of
+// every cell so tilings can be drawn.
+pub struct Tiling {
+    pub adjacency: Vec<Vec<usize>>,
+    pub centers: Vec<(f64, f64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PenroseRhombus {
+    Thin,
+    Thick,
+}
+
+fn link(adjacency: &mut [Vec<usize>], a: usize, b: usize) {
+    if a != b && !adjacency[a].contains(&b) {
+        adjacency[a].push(b);
+        adjacency[b].push(a);
+    }
+}
+
+// Offsets `idx` by `delta` within `0..len`, wrapping around when asked to.
+fn offset(idx: usize, delta: isize, len: usize, wrapping: bool) -> Option<usize> {
+    let moved = idx as isize + delta;
+    if wrapping {
+        Some(moved.rem_euclid(len as isize) as usize)
+    } else if moved >= 0 && moved < len as isize {
+        Some(moved as usize)
+    } else {
+        None
+    }
+}
+
+// Rows of alternating up and down pointing triangles; cell `(r, c)` has index
+// `r * cols + c` and points up when `r + c` is even. Every triangle shares an
+// edge with its left and right neighbours and with one triangle above or
+// below. Wrapping requires an even number of rows and columns so that the
+// orientations still alternate across the seams, and at least four columns
+// so that a triangle's left and right neighbours are different cells.
+pub fn triangular_tiling(
+    rows: usize,
+    cols: usize,
+    wrapping: bool,
+) -> Result<Tiling, CellularAutomatonWorldSizeError> {
+    if rows == 0
+        || cols == 0
+        || (wrapping && (rows < 2 || cols < 4 || rows % 2 == 1 || cols % 2 == 1))
+    {
+        return Err(CellularAutomatonWorldSizeError);
+    }
+
+    let height = 3f64.sqrt() / 2.0;
+    let mut adjacency = vec![Vec::new(); rows * cols];
+    let mut centers = Vec::with_capacity(rows * cols);
+
+    for r in 0..rows {
+        for c in 0..cols {
+            let up = (r + c) % 2 == 0;
+            let idx = r * cols + c;
+
+            if let Some(nc) = offset(c, 1, cols, wrapping) {
+                link(&mut adjacency, idx, r * cols + nc);
+            }
+            if let Some(nr) = offset(r, if up { 1 } else { -1 }, rows, wrapping) {
+                link(&mut adjacency, idx, nr * cols + c);
+            }
+
+            let y = r as f64 * height + if up { height * 2.0 / 3.0 } else { height / 3.0 };
+            centers.push(((c as f64 + 1.0) / 2.0, y));
+        }
+    }
+
+    Ok(Tiling { adjacency, centers })
+}
+
+// The vertices of the trihexagonal tiling. Every unit cell `(r, c)` of the
+// underlying triangular lattice holds three sites with indices
+// `(r * cols + c) * 3 + s`, and each site touches two triangles for four
+// neighbours in total.
+pub fn kagome_lattice(
+    rows: usize,
+    cols: usize,
+    wrapping: bool,
+) -> Result<Tiling, CellularAutomatonWorldSizeError> {
+    if rows == 0 || cols == 0 || (wrapping && (rows < 2 || cols < 2)) {
+        return Err(CellularAutomatonWorldSizeError);
+    }
+
+    let site = |r: usize, c: usize, s: usize| (r * cols + c) * 3 + s;
+    let height = 3f64.sqrt();
+    let mut adjacency = vec![Vec::new(); rows * cols * 3];
+    let mut centers = Vec::with_capacity(rows * cols * 3);
+
+    for r in 0..rows {
+        for c in 0..cols {
+            // Upward triangle inside the unit cell
+            link(&mut adjacency, site(r, c, 0), site(r, c, 1));
+            link(&mut adjacency, site(r, c, 0), site(r, c, 2));
+            link(&mut adjacency, site(r, c, 1), site(r, c, 2));
+
+            // Downward triangle shared with the neighbouring unit cells
+            if let Some(pc) = offset(c, -1, cols, wrapping) {
+                link(&mut adjacency, site(r, c, 0), site(r, pc, 1));
+            }
+            if let Some(pr) = offset(r, -1, rows, wrapping) {
+                link(&mut adjacency, site(r, c, 0), site(pr, c, 2));
+                if let Some(nc) = offset(c, 1, cols, wrapping) {
+                    link(&mut adjacency, site(r, c, 1), site(pr, nc, 2));
+                }
+            }
+
+            let x = 2.0 * c as f64 + r as f64;
+            let y = height * r as f64;
+            centers.push((x, y));
+            centers.push((x + 1.0, y));
+            centers.push((x + 0.5, y + height / 2.0));
+        }
+    }
+
+    Ok(Tiling { adjacency, centers })
+}
+
+type Point = (f64, f64);
+
+fn lerp(a: Point, b: Point, t: f64) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn point_key(p: Point) -> (i64, i64) {
+    ((p.0 * 1e6).round() as i64, (p.1 * 1e6).round() as i64)
+}
+
+fn edge_key(a: Point, b: Point) -> ((i64, i64), (i64, i64)) {
+    let (a, b) = (point_key(a), point_key(b));
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// A Penrose P3 tiling of the unit disc, built by deflating a wheel of ten
+// Robinson triangles `subdivisions` times. Pairs of triangles sharing a base
+// form the rhombi, which become the cells; rhombi that share an edge are
+// neighbours, so interior cells have four neighbours while half-rhombi on
+// the rim have fewer.
+pub fn penrose_rhombus_tiling(subdivisions: usize) -> (Tiling, Vec<PenroseRhombus>) {
+    let phi = (1.0 + 5f64.sqrt()) / 2.0;
+
+    let mut triangles: Vec<(PenroseRhombus, Point, Point, Point)> = (0..10)
+        .map(|i| {
+            let angle = |k: i32| k as f64 * std::f64::consts::PI / 10.0;
+            let b = (angle(2 * i - 1).cos(), angle(2 * i - 1).sin());
+            let c = (angle(2 * i + 1).cos(), angle(2 * i + 1).sin());
+            if i % 2 == 0 {
+                (PenroseRhombus::Thin, (0.0, 0.0), c, b)
+            } else {
+                (PenroseRhombus::Thin, (0.0, 0.0), b, c)
+            }
+        })
+        .collect();
+
+    for _ in 0..subdivisions {
+        triangles = triangles
+            .into_iter()
+            .flat_map(|(kind, a, b, c)| match kind {
+                PenroseRhombus::Thin => {
+                    let p = lerp(a, b, 1.0 / phi);
+                    vec![
+                        (PenroseRhombus::Thin, c, p, b),
+                        (PenroseRhombus::Thick, p, c, a),
+                    ]
+                }
+                PenroseRhombus::Thick => {
+                    let q = lerp(b, a, 1.0 / phi);
+                    let r = lerp(b, c, 1.0 / phi);
+                    vec![
+                        (PenroseRhombus::Thick, r, c, a),
+                        (PenroseRhombus::Thick, q, r, b),
+                        (PenroseRhombus::Thin, r, q, a),
+                    ]
+                }
+            })
+            .collect();
+    }
+
+    let mut cells = HashMap::new();
+    let mut kinds = Vec::new();
+    let mut centers = Vec::new();
+    let mut edges: HashMap<_, Vec<usize>> = HashMap::new();
+
+    for (kind, a, b, c) in triangles {
+        let next = cells.len();
+        let cell = *cells.entry(edge_key(b, c)).or_insert(next);
+        if cell == next {
+            kinds.push(kind);
+            centers.push(lerp(b, c, 0.5));
+        }
+
+        for outer in [edge_key(a, b), edge_key(a, c)] {
+            edges.entry(outer).or_default().push(cell);
+        }
+    }
+
+    let mut adjacency = vec![Vec::new(); kinds.len()];
+    for sharing in edges.values() {
+        if let [a, b] = sharing[..] {
+            link(&mut adjacency, a, b);
+        }
+    }
+
+    (Tiling { adjacency, centers }, kinds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::CellularAutomaton;
+    use crate::graph::GraphCellularAutomaton;
+
+    fn is_symmetric(adjacency: &[Vec<usize>]) -> bool {
+        adjacency
+            .iter()
+            .enumerate()
+            .all(|(i, ns)| ns.iter().all(|&n| adjacency[n].contains(&i)))
+    }
+
+    #[test]
+    fn test_triangular_wrapping() {
+        let tiling = triangular_tiling(4, 6, true).expect("Construction failed");
+
+        assert_eq!(tiling.adjacency.len(), 24);
+        assert!(tiling.adjacency.iter().all(|ns| ns.len() == 3));
+        assert!(is_symmetric(&tiling.adjacency));
+
+        // (0, 0) points up so it shares its base with the triangle below
+        assert!(tiling.adjacency[0].contains(&6));
+        // ...and its left edge with the far end of the row
+        assert!(tiling.adjacency[0].contains(&5));
+    }
+
+    #[test]
+    fn test_triangular_bounded() {
+        let tiling = triangular_tiling(2, 3, false).expect("Construction failed");
+
+        assert_eq!(tiling.adjacency[0], vec![1, 3]);
+        assert_eq!(tiling.adjacency[1], vec![0, 2]);
+        assert_eq!(tiling.adjacency[4], vec![3, 5]);
+        assert_eq!(tiling.adjacency[5], vec![2, 4]);
+
+        assert!(triangular_tiling(3, 4, true).is_err());
+    }
+
+    #[test]
+    fn test_triangular_too_narrow_to_wrap() {
+        // Two columns would make the left and right neighbours one cell
+        assert!(triangular_tiling(2, 2, true).is_err());
+        assert!(triangular_tiling(4, 2, true).is_err());
+        assert!(triangular_tiling(2, 2, false).is_ok());
+
+        let tiling = triangular_tiling(2, 4, true).expect("Construction failed");
+        assert!(tiling.adjacency.iter().all(|ns| ns.len() == 3));
+
+        let kagome = kagome_lattice(2, 2, true).expect("Construction failed");
+        assert!(kagome.adjacency.iter().all(|ns| ns.len() == 4));
+    }
+
+    #[test]
+    fn test_kagome_wrapping() {
+        let tiling = kagome_lattice(3, 4, true).expect("Construction failed");
+
+        assert_eq!(tiling.adjacency.len(), 36);
+        assert!(tiling.adjacency.iter().all(|ns| ns.len() == 4));
+        assert!(is_symmetric(&tiling.adjacency));
+
+        // Neighbouring sites are all exactly one unit apart
+        for (i, ns) in tiling.adjacency.iter().enumerate() {
+            for &n in ns {
+                let (a, b) = (tiling.centers[i], tiling.centers[n]);
+                let dist = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+                let wrapped = dist > 1.5;
+                assert!(wrapped || (dist - 1.0).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_penrose_neighbours() {
+        let (tiling, kinds) = penrose_rhombus_tiling(5);
+
+        assert_eq!(tiling.adjacency.len(), kinds.len());
+        assert!(is_symmetric(&tiling.adjacency));
+        assert!(tiling.adjacency.iter().all(|ns| ns.len() <= 4));
+
+        let interior = tiling.adjacency.iter().filter(|ns| ns.len() == 4).count();
+        assert!(interior * 10 > tiling.adjacency.len() * 8);
+    }
+
+    #[test]
+    fn test_penrose_golden_ratio() {
+        let (_, kinds) = penrose_rhombus_tiling(7);
+
+        let thick = kinds
+            .iter()
+            .filter(|&&k| k == PenroseRhombus::Thick)
+            .count();
+        let thin = kinds.len() - thick;
+        let ratio = thick as f64 / thin as f64;
+
+        assert!((ratio - (1.0 + 5f64.sqrt()) / 2.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_penrose_automaton() {
+        let (tiling, _) = penrose_rhombus_tiling(3);
+        let mut world = vec![false; tiling.adjacency.len()];
+        world[0] = true;
+
+        // Every cell next to a live cell lights up
+        let mut ca = GraphCellularAutomaton::new(world, tiling.adjacency.clone(), |c, ns| {
+            *c || ns.iter().any(|&n| n)
+        })
+        .expect("Construction failed");
+
+        ca.step();
+
+        let live = ca.world().iter().filter(|&&c| c).count();
+        assert_eq!(live, tiling.adjacency[0].len() + 1);
+    }
+}
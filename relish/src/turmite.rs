@@ -0,0 +1,456 @@
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Turn {
+    NoTurn,
+    Right,
+    UTurn,
+    Left,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    fn turn(self, turn: Turn) -> Self {
+        let quarters = match turn {
+            Turn::NoTurn => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        };
+        let headings = [Heading::North, Heading::East, Heading::South, Heading::West];
+        headings[(self as usize + quarters) % 4]
+    }
+
+    fn offset(self) -> (isize, isize) {
+        match self {
+            Heading::North => (-1, 0),
+            Heading::East => (0, 1),
+            Heading::South => (1, 0),
+            Heading::West => (0, -1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TurmiteTransition {
+    pub write: u8,
+    pub turn: Turn,
+    pub next_state: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ant {
+    pub row: usize,
+    pub col: usize,
+    pub heading: Heading,
+    pub state: usize,
+}
+
+impl Ant {
+    pub fn new(row: usize, col: usize, heading: Heading) -> Self {
+        Self {
+            row,
+            col,
+            heading,
+            state: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TurmiteRuleError {
+    InvalidTurn(char),
+    InvalidTable,
+    InconsistentTable,
+    InvalidWorld,
+}
+
+// Transition table indexed by `[state][color]`.
+pub type TurmiteTable = Vec<Vec<TurmiteTransition>>;
+
+pub struct Turmite {
+    world: Vec<Vec<u8>>,
+    ants: Vec<Ant>,
+    table: TurmiteTable,
+    wrapping: bool,
+    generation: usize,
+}
+
+impl Turmite {
+    pub fn new(
+        world: Vec<Vec<u8>>,
+        table: TurmiteTable,
+        ants: Vec<Ant>,
+        wrapping: bool,
+    ) -> Result<Self, CellularAutomatonWorldSizeError> {
+        let height = world.len();
+        let width = world.first().map_or(0, |row| row.len());
+        let colors = table.first().map_or(0, |row| row.len());
+
+        let valid_world = width > 0 && world.iter().all(|row| row.len() == width);
+        let valid_table = colors > 0
+            && table.iter().all(|row| {
+                row.len() == colors
+                    && row
+                        .iter()
+                        .all(|t| (t.write as usize) < colors && t.next_state < table.len())
+            });
+        let valid_ants = ants
+            .iter()
+            .all(|ant| ant.row < height && ant.col < width && ant.state < table.len());
+        let valid_cells = world.iter().flatten().all(|&c| (c as usize) < colors);
+
+        if !(valid_world && valid_table && valid_ants && valid_cells) {
+            return Err(CellularAutomatonWorldSizeError);
+        }
+
+        Ok(Self {
+            world,
+            ants,
+            table,
+            wrapping,
+            generation: 0,
+        })
+    }
+
+    pub fn from_rule_string(
+        world: Vec<Vec<u8>>,
+        rule: &str,
+        ants: Vec<Ant>,
+        wrapping: bool,
+    ) -> Result<Self, TurmiteRuleError> {
+        let table = parse_turmite_rule(rule)?;
+        Self::new(world, table, ants, wrapping).map_err(|_| TurmiteRuleError::InvalidWorld)
+    }
+
+    pub fn ants(&self) -> &[Ant] {
+        &self.ants
+    }
+
+    fn advance(&self, ant: Ant) -> Option<Ant> {
+        let transition = self.table[ant.state][self.world[ant.row][ant.col] as usize];
+        let heading = ant.heading.turn(transition.turn);
+        let (d_row, d_col) = heading.offset();
+
+        let height = self.world.len() as isize;
+        let width = self.world[0].len() as isize;
+        let mut row = ant.row as isize + d_row;
+        let mut col = ant.col as isize + d_col;
+
+        if self.wrapping {
+            row = row.rem_euclid(height);
+            col = col.rem_euclid(width);
+        } else if row < 0 || row >= height || col < 0 || col >= width {
+            return None;
+        }
+
+        Some(Ant {
+            row: row as usize,
+            col: col as usize,
+            heading,
+            state: transition.next_state,
+        })
+    }
+}
+
+impl CellularAutomaton for Turmite {
+    type WorldType = Vec<Vec<u8>>;
+
+    // Moves every ant once, in order. Without wrapping, ants that walk off the
+    // edge of the grid are removed after painting their last cell.
+    fn step(&mut self) -> usize {
+        let mut ants = Vec::with_capacity(self.ants.len());
+
+        for idx in 0..self.ants.len() {
+            let ant = self.ants[idx];
+            let moved = self.advance(ant);
+            self.world[ant.row][ant.col] =
+                self.table[ant.state][self.world[ant.row][ant.col] as usize].write;
+            ants.extend(moved);
+        }
+
+        self.ants = ants;
+        self.generation += 1;
+        self.generation
+    }
+
+    fn size(&self) -> Vec<usize> {
+        vec![self.world.len(), self.world[0].len()]
+    }
+
+    fn age(&self) -> usize {
+        self.generation
+    }
+
+    fn world(&self) -> Self::WorldType {
+        self.world.clone()
+    }
+}
+
+// Parses single state rule strings such as `RL` or `LLRR`. The letter at
+// index `n` gives the turn taken on a cell of color `n` (`L`, `R`, `N` for no
+// turn or `U` for a u-turn), and the cell is then advanced to the next color.
+pub fn parse_turmite_rule(rule: &str) -> Result<TurmiteTable, TurmiteRuleError> {
+    let colors = rule.chars().count();
+    if colors == 0 || colors > u8::MAX as usize + 1 {
+        return Err(TurmiteRuleError::InvalidTable);
+    }
+
+    let row = rule
+        .chars()
+        .enumerate()
+        .map(|(color, c)| {
+            let turn = match c.to_ascii_uppercase() {
+                'L' => Turn::Left,
+                'R' => Turn::Right,
+                'N' => Turn::NoTurn,
+                'U' => Turn::UTurn,
+                _ => return Err(TurmiteRuleError::InvalidTurn(c)),
+            };
+            Ok(TurmiteTransition {
+                write: ((color + 1) % colors) as u8,
+                turn,
+                next_state: 0,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(vec![row])
+}
+
+// Parses a full transition table in the Golly turmite notation, e.g.
+// `{{{1,2,0},{0,8,0}}}` for Langton's ant. Each state holds one
+// `{write, turn, next_state}` triple per color, where the turn is one of
+// `1` (no turn), `2` (right), `4` (u-turn) or `8` (left).
+pub fn parse_turmite_table(table: &str) -> Result<TurmiteTable, TurmiteRuleError> {
+    let numbers = table
+        .split(|c: char| c == '{' || c == '}' || c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<usize>()
+                .map_err(|_| TurmiteRuleError::InvalidTable)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Count the triples opened inside each state so ragged tables are caught
+    let mut depth = 0;
+    let mut triples_per_state = Vec::new();
+    for c in table.chars() {
+        match c {
+            '{' => {
+                depth += 1;
+                match depth {
+                    2 => triples_per_state.push(0),
+                    3 => *triples_per_state.last_mut().unwrap() += 1,
+                    1 => {}
+                    _ => return Err(TurmiteRuleError::InvalidTable),
+                }
+            }
+            '}' if depth == 0 => return Err(TurmiteRuleError::InvalidTable),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    let states = triples_per_state.len();
+    let colors = triples_per_state.first().copied().unwrap_or(0);
+    if depth != 0 || colors == 0 || numbers.len() != states * colors * 3 {
+        return Err(TurmiteRuleError::InvalidTable);
+    }
+    if triples_per_state.iter().any(|&n| n != colors) {
+        return Err(TurmiteRuleError::InconsistentTable);
+    }
+
+    let transitions = numbers
+        .chunks(3)
+        .map(|t| {
+            let turn = match t[1] {
+                1 => Turn::NoTurn,
+                2 => Turn::Right,
+                4 => Turn::UTurn,
+                8 => Turn::Left,
+                _ => return Err(TurmiteRuleError::InvalidTable),
+            };
+            if t[0] >= colors || t[2] >= states {
+                return Err(TurmiteRuleError::InconsistentTable);
+            }
+            Ok(TurmiteTransition {
+                write: t[0] as u8,
+                turn,
+                next_state: t[2],
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(transitions.chunks(colors).map(|row| row.to_vec()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_langtons_ant_first_steps() {
+        let ant = Ant::new(2, 2, Heading::North);
+        let mut turmite = Turmite::from_rule_string(vec![vec![0; 5]; 5], "RL", vec![ant], false)
+            .expect("Construction failed");
+
+        turmite.step();
+
+        let mut expected = vec![vec![0; 5]; 5];
+        expected[2][2] = 1;
+        assert_eq!(turmite.world(), expected);
+        assert_eq!(turmite.ants()[0], Ant::new(2, 3, Heading::East));
+
+        for _ in 0..3 {
+            turmite.step();
+        }
+
+        // Four right turns bring the ant back to where it started
+        expected[2][3] = 1;
+        expected[3][3] = 1;
+        expected[3][2] = 1;
+        assert_eq!(turmite.world(), expected);
+        assert_eq!(turmite.ants()[0], Ant::new(2, 2, Heading::North));
+
+        // Now on a painted cell, it turns left and erases it
+        turmite.step();
+        expected[2][2] = 0;
+        assert_eq!(turmite.world(), expected);
+        assert_eq!(turmite.ants()[0], Ant::new(2, 1, Heading::West));
+        assert_eq!(turmite.age(), 5);
+    }
+
+    #[test]
+    fn test_langtons_ant_highway() {
+        let ant = Ant::new(64, 64, Heading::North);
+        let mut turmite = Turmite::from_rule_string(vec![vec![0; 128]; 128], "RL", vec![ant], true)
+            .expect("Construction failed");
+
+        for _ in 0..11000 {
+            turmite.step();
+        }
+        let before = turmite.ants()[0];
+
+        for _ in 0..104 {
+            turmite.step();
+        }
+        let after = turmite.ants()[0];
+
+        // The highway repeats every 104 steps, two cells further along a diagonal
+        assert_eq!(before.heading, after.heading);
+        assert_eq!(before.row.abs_diff(after.row), 2);
+        assert_eq!(before.col.abs_diff(after.col), 2);
+    }
+
+    #[test]
+    fn test_parse_rule_string() {
+        let table = parse_turmite_rule("LLRR").expect("Parse failed");
+
+        assert_eq!(table.len(), 1);
+        assert_eq!(table[0].len(), 4);
+        assert_eq!(
+            table[0][3],
+            TurmiteTransition {
+                write: 0,
+                turn: Turn::Right,
+                next_state: 0
+            }
+        );
+
+        assert!(parse_turmite_rule("RX").is_err());
+        assert!(parse_turmite_rule("").is_err());
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let langton = parse_turmite_table("{{{1, 2, 0}, {0, 8, 0}}}").expect("Parse failed");
+        assert_eq!(langton, parse_turmite_rule("RL").unwrap());
+
+        let spiral =
+            parse_turmite_table("{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}").expect("Parse failed");
+        assert_eq!(spiral.len(), 2);
+        assert_eq!(
+            spiral[1][1],
+            TurmiteTransition {
+                write: 0,
+                turn: Turn::NoTurn,
+                next_state: 0
+            }
+        );
+
+        assert!(parse_turmite_table("{{{1,3,0},{0,8,0}}}").is_err());
+        assert!(parse_turmite_table("{{{1,2,1},{0,8,0}}}").is_err());
+        assert!(parse_turmite_table("{{1,2,0}}").is_err());
+    }
+
+    #[test]
+    fn test_two_state_turmite() {
+        let table = parse_turmite_table("{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}}").unwrap();
+        let ant = Ant::new(2, 2, Heading::North);
+        let mut turmite =
+            Turmite::new(vec![vec![0; 5]; 5], table, vec![ant], true).expect("Construction failed");
+
+        turmite.step();
+        assert_eq!(
+            turmite.ants()[0],
+            Ant {
+                row: 2,
+                col: 1,
+                heading: Heading::West,
+                state: 1
+            }
+        );
+
+        turmite.step();
+        assert_eq!(
+            turmite.ants()[0],
+            Ant {
+                row: 1,
+                col: 1,
+                heading: Heading::North,
+                state: 1
+            }
+        );
+        assert_eq!(turmite.world()[2][2], 1);
+        assert_eq!(turmite.world()[2][1], 1);
+    }
+
+    #[test]
+    fn test_multiple_ants_and_edges() {
+        let ants = vec![
+            Ant::new(0, 0, Heading::West),
+            Ant::new(1, 1, Heading::North),
+        ];
+        let mut turmite = Turmite::from_rule_string(vec![vec![0; 3]; 3], "NN", ants, false)
+            .expect("Construction failed");
+
+        turmite.step();
+
+        // The first ant walked off the grid, the second carried on north
+        assert_eq!(turmite.ants(), &[Ant::new(0, 1, Heading::North)]);
+        assert_eq!(
+            turmite.world(),
+            vec![vec![1, 0, 0], vec![0, 1, 0], vec![0; 3]]
+        );
+
+        turmite.step();
+        assert!(turmite.ants().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_turmite() {
+        let ant = Ant::new(5, 0, Heading::North);
+        assert!(Turmite::from_rule_string(vec![vec![0; 3]; 3], "RL", vec![ant], true).is_err());
+
+        let ant = Ant::new(0, 0, Heading::North);
+        assert!(Turmite::from_rule_string(vec![vec![2; 3]; 3], "RL", vec![ant], true).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use rand::{Rng, SeedableRng};
+
+use crate::automaton::{check_probability, InvalidProbabilityError};
+use crate::stochastic::StochasticRng;
+
+// How `step()` picks the cells to update. Whatever the scheme, one call to
+// `step()` advances `age()` by exactly one:
+//
+// - `Synchronous`: every cell is updated at once from the previous world.
+// - `RandomSequential`: one Monte Carlo sweep of N single-cell updates, each
+//   on a cell picked uniformly at random (with replacement) and applied in
+//   place, where N is the number of cells.
+// - `LineSweep`: every cell is updated in place once, in index (row-major)
+//   order, so later cells already see their updated neighbours.
+// - `RandomIndependent(alpha)`: each cell is updated with probability `alpha`,
+//   all chosen cells at once from the previous world.
+// - `Clock(max_period)`: each cell gets a period in `1..=max_period` and a
+//   phase when the scheme is set; one tick updates, at once, the cells whose
+//   clock fires on it. `max_period` must be at least 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateScheme {
+    Synchronous,
+    RandomSequential,
+    LineSweep,
+    RandomIndependent(f64),
+    Clock(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpdateSchemeError {
+    Probability(InvalidProbabilityError),
+    // A `Clock` scheme with a maximum period of 0
+    ZeroPeriod,
+}
+
+pub(crate) enum UpdatePlan {
+    // Update the cells marked in the mask (all of them for `None`) from the
+    // previous world
+    Synchronous(Option<Vec<bool>>),
+    // Update the cells one after another in place, in this order
+    Sequential(Vec<usize>),
+}
+
+pub(crate) struct UpdateSchedule {
+    scheme: UpdateScheme,
+    rng: StochasticRng,
+    clocks: Vec<(usize, usize)>,
+}
+
+impl UpdateSchedule {
+    pub(crate) fn new(
+        scheme: UpdateScheme,
+        seed: u64,
+        cells: usize,
+    ) -> Result<Self, UpdateSchemeError> {
+        match scheme {
+            UpdateScheme::RandomIndependent(alpha) => {
+                check_probability("alpha", alpha).map_err(UpdateSchemeError::Probability)?;
+            }
+            UpdateScheme::Clock(0) => return Err(UpdateSchemeError::ZeroPeriod),
+            _ => {}
+        }
+
+        let mut rng = StochasticRng::seed_from_u64(seed);
+        let clocks = match scheme {
+            UpdateScheme::Clock(max_period) => (0..cells)
+                .map(|_| {
+                    let period = rng.gen_range(1..=max_period);
+                    (period, rng.gen_range(0..period))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            scheme,
+            rng,
+            clocks,
+        })
+    }
+
+    pub(crate) fn scheme(&self) -> UpdateScheme {
+        self.scheme
+    }
+
+    pub(crate) fn plan(&mut self, generation: usize, cells: usize) -> UpdatePlan {
+        match self.scheme {
+            UpdateScheme::Synchronous => UpdatePlan::Synchronous(None),
+            UpdateScheme::RandomSequential => {
+                UpdatePlan::Sequential((0..cells).map(|_| self.rng.gen_range(0..cells)).collect())
+            }
+            UpdateScheme::LineSweep => UpdatePlan::Sequential((0..cells).collect()),
+            UpdateScheme::RandomIndependent(alpha) => UpdatePlan::Synchronous(Some(
+                (0..cells).map(|_| self.rng.gen_bool(alpha)).collect(),
+            )),
+            UpdateScheme::Clock(_) => UpdatePlan::Synchronous(Some(
+                self.clocks
+                    .iter()
+                    .map(|&(period, phase)| generation % period == phase)
+                    .collect(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::CellularAutomaton;
+    use crate::conway::ConwayCellularAutomaton;
+    use crate::dim1::{CellularAutomaton1d, Neighbors1d};
+    use crate::elementary::ElementaryCellularAutomaton;
+
+    fn shifter(world: Vec<bool>) -> CellularAutomaton1d<bool, 1> {
+        CellularAutomaton1d::<bool, 1>::new(
+            world,
+            |x| x[0],
+            |world, i| {
+                if i == 0 {
+                    Neighbors1d::Edge
+                } else {
+                    Neighbors1d::Neighborhood([world[i - 1]])
+                }
+            },
+        )
+        .expect("Construction failed")
+    }
+
+    fn flipper(world: Vec<bool>) -> CellularAutomaton1d<bool, 1> {
+        CellularAutomaton1d::<bool, 1>::new(
+            world,
+            |x| !x[0],
+            |world, i| Neighbors1d::Neighborhood([world[i]]),
+        )
+        .expect("Construction failed")
+    }
+
+    #[test]
+    fn test_line_sweep_propagates_in_place() {
+        let mut ca = shifter(vec![true, false, false, false])
+            .with_update_scheme(UpdateScheme::LineSweep, 0)
+            .expect("Invalid update scheme");
+
+        ca.step();
+
+        assert_eq!(ca.world(), vec![true; 4]);
+        assert_eq!(ca.age(), 1);
+        assert_eq!(ca.update_scheme(), UpdateScheme::LineSweep);
+    }
+
+    #[test]
+    fn test_synchronous_is_default() {
+        let mut ca = shifter(vec![true, false, false, false]);
+        assert_eq!(ca.update_scheme(), UpdateScheme::Synchronous);
+
+        ca.step();
+        assert_eq!(ca.world(), vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn test_random_independent_limits() {
+        let mut world = vec![false; 21];
+        world[10] = true;
+
+        let mut sync = ElementaryCellularAutomaton(world.clone(), 30).expect("Construction failed");
+        let mut all = ElementaryCellularAutomaton(world.clone(), 30)
+            .expect("Construction failed")
+            .with_update_scheme(UpdateScheme::RandomIndependent(1.0), 3)
+            .expect("Invalid update scheme");
+        let mut none = ElementaryCellularAutomaton(world.clone(), 30)
+            .expect("Construction failed")
+            .with_update_scheme(UpdateScheme::RandomIndependent(0.0), 3)
+            .expect("Invalid update scheme");
+
+        for _ in 0..5 {
+            sync.step();
+            all.step();
+            none.step();
+        }
+
+        assert_eq!(all.world(), sync.world());
+        assert_eq!(none.world(), world);
+        assert_eq!(none.age(), 5);
+    }
+
+    #[test]
+    fn test_random_sequential_sweep() {
+        let mut a = flipper(vec![false; 50])
+            .with_update_scheme(UpdateScheme::RandomSequential, 8)
+            .expect("Invalid update scheme");
+        let mut b = flipper(vec![false; 50])
+            .with_update_scheme(UpdateScheme::RandomSequential, 8)
+            .expect("Invalid update scheme");
+
+        a.step();
+        b.step();
+        assert_eq!(a.world(), b.world());
+
+        // Cells are picked with replacement, so some were flipped back or
+        // never picked at all
+        let flipped = a.world().iter().filter(|&&c| c).count();
+        assert!(flipped > 0 && flipped < 50);
+    }
+
+    #[test]
+    fn test_clock_updates() {
+        let mut unit = flipper(vec![false; 10])
+            .with_update_scheme(UpdateScheme::Clock(1), 4)
+            .expect("Invalid update scheme");
+        unit.step();
+        assert_eq!(unit.world(), vec![true; 10]);
+
+        // Over a common multiple of all periods every cell fires a whole
+        // number of times per period, so each one has flipped an exact count
+        let mut slow = flipper(vec![false; 10])
+            .with_update_scheme(UpdateScheme::Clock(3), 4)
+            .expect("Invalid update scheme");
+        let mut fires = [0; 10];
+        for _ in 0..6 {
+            let before = slow.world();
+            slow.step();
+            for (count, (b, a)) in fires.iter_mut().zip(before.iter().zip(slow.world())) {
+                *count += (*b != a) as usize;
+            }
+        }
+        assert!(fires.iter().all(|&f| f == 2 || f == 3 || f == 6));
+        assert!(fires.iter().any(|&f| f != 6));
+    }
+
+    #[test]
+    fn test_invalid_schemes() {
+        for alpha in [-0.5, 1.5, f64::NAN] {
+            assert!(matches!(
+                flipper(vec![false; 4])
+                    .with_update_scheme(UpdateScheme::RandomIndependent(alpha), 1),
+                Err(UpdateSchemeError::Probability(_))
+            ));
+        }
+        assert!(matches!(
+            flipper(vec![false; 4]).with_update_scheme(UpdateScheme::Clock(0), 1),
+            Err(UpdateSchemeError::ZeroPeriod)
+        ));
+
+        let world = vec![vec![false; 3]; 3];
+        assert!(matches!(
+            ConwayCellularAutomaton(world, true)
+                .expect("Construction failed")
+                .with_update_scheme(UpdateScheme::Clock(0), 1),
+            Err(UpdateSchemeError::ZeroPeriod)
+        ));
+    }
+
+    #[test]
+    fn test_conway_line_sweep() {
+        let mut world = vec![vec![false; 5]; 5];
+        world[2][1] = true;
+        world[2][2] = true;
+        world[2][3] = true;
+
+        let mut sync = ConwayCellularAutomaton(world.clone(), false).expect("Construction failed");
+        let mut sweep = ConwayCellularAutomaton(world, false)
+            .expect("Construction failed")
+            .with_update_scheme(UpdateScheme::LineSweep, 0)
+            .expect("Invalid update scheme");
+
+        sync.step();
+        sweep.step();
+
+        // Both sweep and synchronous updates give birth at (1, 2), but the
+        // sweep then already counts it as a neighbour of (1, 3)
+        assert!(sync.world()[1][2] && sweep.world()[1][2]);
+        assert!(!sync.world()[1][3] && sweep.world()[1][3]);
+    }
+}
@@ -0,0 +1,195 @@
+#![allow(unused_imports)]
+use crate::automaton::{CellularAutomaton, CellularAutomatonWorldSizeError};
+
+use crate::dim2::{CellularAutomaton2d, Neighbors2d};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WireworldCell {
+    #[default]
+    Empty,
+    Head,
+    Tail,
+    Conductor,
+}
+
+#[derive(Debug, Clone)]
+pub struct WireworldParseError {
+    pub line: usize,
+    pub column: usize,
+}
+
+fn wireworld_neighbors(
+    world: &[Vec<WireworldCell>],
+    i: usize,
+    j: usize,
+) -> Neighbors2d<WireworldCell, 3, 3> {
+    let height = world.len() as isize;
+    let width = world[0].len() as isize;
+
+    let mut neighbors = [[WireworldCell::Empty; 3]; 3];
+
+    for (x, row) in neighbors.iter_mut().enumerate() {
+        for (y, cell) in row.iter_mut().enumerate() {
+            let n_i = i as isize + x as isize - 1;
+            let n_j = j as isize + y as isize - 1;
+
+            if n_i >= 0 && n_i < height && n_j >= 0 && n_j < width {
+                *cell = world[n_i as usize][n_j as usize];
+            }
+        }
+    }
+
+    Neighbors2d::Neighborhood(neighbors)
+}
+
+fn wireworld_evolve(neighbors: [[WireworldCell; 3]; 3]) -> WireworldCell {
+    match neighbors[1][1] {
+        WireworldCell::Empty => WireworldCell::Empty,
+        WireworldCell::Head => WireworldCell::Tail,
+        WireworldCell::Tail => WireworldCell::Conductor,
+        WireworldCell::Conductor => {
+            let heads = neighbors
+                .iter()
+                .flat_map(|row| row.iter())
+                .filter(|&&cell| cell == WireworldCell::Head)
+                .count();
+
+            if heads == 1 || heads == 2 {
+                WireworldCell::Head
+            } else {
+                WireworldCell::Conductor
+            }
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn WireworldCellularAutomaton(
+    world: Vec<Vec<WireworldCell>>,
+) -> Result<CellularAutomaton2d<WireworldCell, 3, 3>, CellularAutomatonWorldSizeError> {
+    CellularAutomaton2d::<WireworldCell, 3, 3>::new(world, wireworld_evolve, |world, i, j| {
+        wireworld_neighbors(world, i, j)
+    })
+}
+
+// Reads a circuit drawn with `#` for conductor, `H` for an electron head,
+// `t` for an electron tail and a space or `.` for empty cells. Short lines
+// are padded with empty cells so the world is rectangular.
+pub fn parse_wireworld(circuit: &str) -> Result<Vec<Vec<WireworldCell>>, WireworldParseError> {
+    let mut world = circuit
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.chars()
+                .enumerate()
+                .map(|(j, c)| match c {
+                    ' ' | '.' => Ok(WireworldCell::Empty),
+                    'H' => Ok(WireworldCell::Head),
+                    't' => Ok(WireworldCell::Tail),
+                    '#' => Ok(WireworldCell::Conductor),
+                    _ => Err(WireworldParseError {
+                        line: i + 1,
+                        column: j + 1,
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let width = world.iter().map(|row| row.len()).max().unwrap_or(0);
+    world
+        .iter_mut()
+        .for_each(|row| row.resize(width, WireworldCell::Empty));
+
+    Ok(world)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Steps the circuit and records the generations at which an electron
+    // head reaches cell `(i, j)`
+    fn heads_at(circuit: &str, i: usize, j: usize, steps: usize) -> Vec<usize> {
+        let world = parse_wireworld(circuit).expect("Parse failed");
+        let mut ca = WireworldCellularAutomaton(world).expect("Construction failed");
+
+        let mut arrivals = Vec::new();
+        for _ in 0..steps {
+            let generation = ca.step();
+            if ca.world()[i][j] == WireworldCell::Head {
+                arrivals.push(generation);
+            }
+        }
+        arrivals
+    }
+
+    #[test]
+    fn test_parse_wireworld() {
+        let world = parse_wireworld("tH#\n #").expect("Parse failed");
+
+        assert_eq!(
+            world,
+            vec![
+                vec![
+                    WireworldCell::Tail,
+                    WireworldCell::Head,
+                    WireworldCell::Conductor
+                ],
+                vec![
+                    WireworldCell::Empty,
+                    WireworldCell::Conductor,
+                    WireworldCell::Empty
+                ],
+            ]
+        );
+
+        let err = parse_wireworld("##\n#x#").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+    }
+
+    #[test]
+    fn test_electron_travels_wire() {
+        let world = parse_wireworld("tH###").expect("Parse failed");
+        let mut ca = WireworldCellularAutomaton(world).expect("Construction failed");
+
+        ca.step();
+        assert_eq!(ca.world(), parse_wireworld("#tH##").unwrap());
+
+        ca.step();
+        assert_eq!(ca.world(), parse_wireworld("##tH#").unwrap());
+    }
+
+    #[test]
+    fn test_diode() {
+        let forward = "  ##    \ntH# ####\n  ##    ";
+        let reverse = "  ##    \n### ##Ht\n  ##    ";
+
+        assert_eq!(heads_at(forward, 1, 7, 12), vec![6]);
+        assert_eq!(heads_at(reverse, 1, 0, 12), vec![]);
+    }
+
+    #[test]
+    fn test_clock() {
+        let clock = " tH \n#  #####\n ## ";
+
+        assert_eq!(heads_at(clock, 1, 7, 30), vec![5, 11, 17, 23, 29]);
+    }
+
+    #[test]
+    fn test_xor_gate() {
+        let gate = |a: bool, b: bool| {
+            let input = |on: bool| if on { "tH##" } else { "####" };
+            format!(
+                "\n{}\n    ## #\n     ## ####\n    ## #\n{}\n",
+                input(a),
+                input(b)
+            )
+        };
+
+        assert_eq!(heads_at(&gate(false, false), 3, 11, 20).len(), 0);
+        assert_eq!(heads_at(&gate(true, false), 3, 11, 20).len(), 1);
+        assert_eq!(heads_at(&gate(false, true), 3, 11, 20).len(), 1);
+        assert_eq!(heads_at(&gate(true, true), 3, 11, 20).len(), 0);
+    }
+}